@@ -1,14 +1,31 @@
 mod qsl_adif_helper;
+mod qsl_attachment;
+mod qsl_cabrillo;
+mod qsl_callsign;
+mod qsl_card;
 mod qsl_context;
+mod qsl_country;
+mod qsl_frequency;
+mod qsl_grid;
+mod qsl_history;
 mod qsl_manage_ui;
 mod qsl_manager;
+mod qsl_migration;
+mod qsl_province;
+mod qsl_station;
 mod qsl_template;
 mod qsl_type;
 
 use crate::qsl_context::QSLContext;
-use crate::qsl_manage_ui::{edit_record_dialog, show_qsl_table};
+use crate::qsl_manage_ui::{
+    country_file_dialog, edit_record_dialog, equipment_presets_dialog, filter_records,
+    history_dialog, provinces_dialog, search_callsign_dialog, search_dialog, show_qsl_table,
+    station_profiles_dialog, statistics_dialog, tag_filter_dialog, time_zone_dialog, trash_dialog,
+    trash_retention_dialog,
+};
 use crate::qsl_manager::QSLManager;
-use crate::qsl_type::Usage;
+use crate::qsl_type::{QSLFilter, Usage};
+use chrono::NaiveDate;
 use cursive::event::{Event, Key};
 use cursive::reexports::log;
 use cursive::reexports::log::LevelFilter;
@@ -16,12 +33,30 @@ use cursive::views::Dialog;
 use cursive::{logger, menu};
 use std::env;
 use std::fs::File;
+use std::io;
 use std::path::Path;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // `--tag <name>` limits any export to the records with that tag.
+    let filter = match args.iter().position(|arg| arg == "--tag") {
+        Some(index) if index + 1 < args.len() => {
+            let tag = args.remove(index + 1);
+            args.remove(index);
+            QSLFilter::Tag(tag)
+        }
+        Some(_) => {
+            eprintln!("Require a tag name");
+            return;
+        }
+        None => QSLFilter::All,
+    };
     if args.len() < 2 {
-        println!("Usage: qsl <sqlite file path> [--html <path> | --typst <path> | --adif <path>]");
+        println!(
+            "Usage: qsl <sqlite file path> [--html <path> | --typst <path> | --adif <path> \
+             | --cabrillo <path> <contest id> [<from> <to>] \
+             | --import-adif <path>] [--tag <name>]"
+        );
         return;
     }
     let db_file_path = args[1].to_string();
@@ -33,15 +68,19 @@ fn main() {
             Usage::TYPST
         } else if arg == "--adif" {
             Usage::ADIF
+        } else if arg == "--cabrillo" {
+            Usage::CABRILLO
+        } else if arg == "--import-adif" {
+            Usage::IMPORT
         } else {
             Usage::UI
         }
     } else {
         Usage::UI
     };
-    let output_path = if mode != Usage::UI {
+    let file_path = if mode != Usage::UI {
         if args.len() < 4 {
-            eprintln!("Require a path");
+            eprintln!("Require a file path");
             return;
         }
         args[3].to_string()
@@ -61,7 +100,7 @@ fn main() {
 
             match mode {
                 Usage::HTML => {
-                    let path = Path::new(&output_path);
+                    let path = Path::new(&file_path);
                     if !path.exists() {
                         eprintln!("Folder not exists.");
                         return;
@@ -74,7 +113,7 @@ fn main() {
                         eprintln!("The provided directory is not empty.");
                         return;
                     }
-                    match qsl_manager.output_html(path) {
+                    match qsl_manager.output_html(path, &filter) {
                         Ok(()) => {
                             println!("Successful outputting html.");
                             return;
@@ -85,8 +124,8 @@ fn main() {
                         }
                     }
                 }
-                Usage::TYPST => match File::create_new(output_path) {
-                    Ok(mut file) => match qsl_manager.output_typst(&mut file) {
+                Usage::TYPST => match File::create_new(file_path) {
+                    Ok(mut file) => match qsl_manager.output_typst(&mut file, &filter) {
                         Ok(()) => {
                             println!("Successful writing to file.");
                             return;
@@ -102,8 +141,8 @@ fn main() {
                         return;
                     }
                 },
-                Usage::ADIF => match File::create_new(output_path) {
-                    Ok(mut file) => match qsl_manager.output_adif(&mut file) {
+                Usage::ADIF => match File::create_new(file_path) {
+                    Ok(mut file) => match qsl_manager.output_adif(&mut file, &filter) {
                         Ok(()) => {
                             println!("Successful writing to file.");
                             return;
@@ -119,6 +158,72 @@ fn main() {
                         return;
                     }
                 },
+                Usage::CABRILLO => {
+                    let Some(contest_id) = args.get(4) else {
+                        eprintln!("Require a contest id");
+                        return;
+                    };
+                    let mut dates = Vec::new();
+                    for date in args.iter().skip(5).take(2) {
+                        match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                            Ok(date) => dates.push(date),
+                            Err(err) => {
+                                eprintln!("Failed to parse date {date}: {err}");
+                                return;
+                            }
+                        }
+                    }
+                    let header = match qsl_manager
+                        .cabrillo_header(&contest_id.to_uppercase())
+                        .prompt(&mut io::stdin().lock(), &mut io::stdout())
+                    {
+                        Ok(header) => header,
+                        Err(err) => {
+                            eprintln!("Failed to read the header: {err}");
+                            return;
+                        }
+                    };
+                    match File::create_new(file_path) {
+                        Ok(mut file) => match qsl_manager.output_cabrillo(
+                            &mut file,
+                            &header,
+                            dates.first().copied(),
+                            dates.get(1).copied(),
+                            &filter,
+                        ) {
+                            Ok(()) => {
+                                println!("Successful writing to file.");
+                                return;
+                            }
+                            Err(err) => {
+                                eprintln!("Failed to write to the file: {err}");
+                                return;
+                            }
+                        },
+
+                        Err(err) => {
+                            eprintln!("Failed to create the file: {err}");
+                            return;
+                        }
+                    }
+                }
+                Usage::IMPORT => match File::open(file_path) {
+                    Ok(mut file) => match qsl_manager.import_adif(&mut file) {
+                        Ok(()) => {
+                            println!("Successful reading the file.");
+                            return;
+                        }
+                        Err(err) => {
+                            eprintln!("Failed to read the file: {err}");
+                            return;
+                        }
+                    },
+
+                    Err(err) => {
+                        eprintln!("Failed to open the file: {err}");
+                        return;
+                    }
+                },
                 Usage::UI => {
                     let mut siv = cursive::default();
                     logger::set_internal_filter_level(LevelFilter::Warn);
@@ -135,10 +240,39 @@ fn main() {
                                 .leaf("New", move |s| {
                                     edit_record_dialog(s, None);
                                 })
+                                .leaf("Statistics", statistics_dialog)
+                                .leaf("Provinces worked", provinces_dialog)
+                                .leaf("History", |s| history_dialog(s, None))
+                                .leaf("Trash", trash_dialog)
                                 .leaf("Output to html", move |s| {
                                     s.add_layer(Dialog::info("Coming soon..."))
                                 }),
                         )
+                        .add_subtree(
+                            "Filter",
+                            menu::Tree::new()
+                                .leaf(QSLFilter::All.to_string(), |s| {
+                                    filter_records(s, QSLFilter::All)
+                                })
+                                .leaf(QSLFilter::CardsOwed.to_string(), |s| {
+                                    filter_records(s, QSLFilter::CardsOwed)
+                                })
+                                .leaf(QSLFilter::CardsAwaited.to_string(), |s| {
+                                    filter_records(s, QSLFilter::CardsAwaited)
+                                })
+                                .leaf("Callsign...", search_callsign_dialog)
+                                .leaf("Tag...", tag_filter_dialog)
+                                .leaf("Search...", search_dialog),
+                        )
+                        .add_subtree(
+                            "Setting",
+                            menu::Tree::new()
+                                .leaf("Station profiles", station_profiles_dialog)
+                                .leaf("Equipment presets", equipment_presets_dialog)
+                                .leaf("Country file", country_file_dialog)
+                                .leaf("Time zone", time_zone_dialog)
+                                .leaf("Trash retention", trash_retention_dialog),
+                        )
                         .add_subtree(
                             "Help",
                             menu::Tree::new()
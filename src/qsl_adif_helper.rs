@@ -1,11 +1,12 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 
 pub fn adif_generate_line(k: &str, v: &str) -> String {
     let len = v.len();
     format!("<{k}:{len}>{v} ")
 }
 
-pub fn adif_generate_header(datetime: &DateTime<Local>) -> String {
+pub fn adif_generate_header(datetime: &DateTime<Utc>) -> String {
     let mut str = adif_generate_line("ADIF_VER", "3.1.4");
     str.push_str("\n");
     str.push_str(&adif_generate_line("PROGRAMID", "BenderBlo1g qsl_recorder"));
@@ -20,3 +21,298 @@ pub fn adif_generate_header(datetime: &DateTime<Local>) -> String {
     str.push_str("<EOH>\n");
     str
 }
+
+/// Split an ADIF (.adi) document into records of upper-cased field names and values.
+/// Fields before `<EOH>` belong to the header and are dropped. Each record is
+/// reported separately so one broken record does not abort the whole file.
+pub fn adif_parse(content: &[u8]) -> Vec<Result<HashMap<String, String>, String>> {
+    let mut records = Vec::new();
+    let mut fields = HashMap::new();
+    let mut error: Option<String> = None;
+    let mut pos = 0;
+
+    while let Some(start) = content[pos..].iter().position(|&c| c == b'<') {
+        let tag_start = pos + start + 1;
+        let tag_end = match content[tag_start..].iter().position(|&c| c == b'>') {
+            Some(end) => tag_start + end,
+            None => {
+                error.get_or_insert("Unterminated tag at end of file".to_string());
+                break;
+            }
+        };
+        let tag = String::from_utf8_lossy(&content[tag_start..tag_end]);
+        pos = tag_end + 1;
+
+        let mut spec = tag.split(':');
+        let name = spec.next().unwrap_or_default().trim().to_uppercase();
+        match name.as_str() {
+            "EOH" => {
+                fields.clear();
+                error = None;
+                continue;
+            }
+            "EOR" => {
+                records.push(match error.take() {
+                    Some(e) => Err(e),
+                    None => Ok(std::mem::take(&mut fields)),
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        // The optional type indicator after the length is not needed, values are kept as text.
+        let length = match spec.next().map(|l| l.trim().parse::<usize>()) {
+            Some(Ok(length)) => length,
+            Some(Err(e)) => {
+                error.get_or_insert(format!("Invalid length in field <{tag}>: {e}"));
+                continue;
+            }
+            None => {
+                error.get_or_insert(format!("Field <{tag}> has no length"));
+                continue;
+            }
+        };
+        if pos + length > content.len() {
+            error.get_or_insert(format!("Field {name} is longer than the rest of the file"));
+            break;
+        }
+        let value = String::from_utf8_lossy(&content[pos..pos + length]).to_string();
+        pos += length;
+        fields.insert(name, value);
+    }
+
+    if error.is_some() || !fields.is_empty() {
+        records.push(Err(
+            error.unwrap_or("Last record is missing <EOR>".to_string())
+        ));
+    }
+    records
+}
+
+/// ADIF 3.1.4 MODE enumeration, each with the SUBMODE values it allows.
+pub const ADIF_MODES: &[(&str, &[&str])] = &[
+    ("AM", &[]),
+    ("ARDOP", &[]),
+    ("ATV", &[]),
+    ("CHIP", &["CHIP64", "CHIP128"]),
+    ("CLO", &[]),
+    ("CONTESTI", &[]),
+    ("CW", &["PCW"]),
+    ("DIGITALVOICE", &["C4FM", "DMR", "DSTAR", "FREEDV", "M17"]),
+    (
+        "DOMINO",
+        &[
+            "DOM-M", "DOM4", "DOM5", "DOM8", "DOM11", "DOM16", "DOM22", "DOM44", "DOM88",
+            "DOMINOEX", "DOMINOF",
+        ],
+    ),
+    (
+        "DYNAMIC",
+        &["VARA HF", "VARA SATELLITE", "VARA FM 1200", "VARA FM 9600"],
+    ),
+    ("FAX", &[]),
+    ("FM", &[]),
+    ("FSK441", &[]),
+    ("FT8", &[]),
+    (
+        "HELL",
+        &[
+            "FMHELL", "FSKHELL", "HELL80", "HELLX5", "HELLX9", "HFSK", "PSKHELL", "SLOWHELL",
+        ],
+    ),
+    ("ISCAT", &["ISCAT-A", "ISCAT-B"]),
+    (
+        "JT4",
+        &["JT4A", "JT4B", "JT4C", "JT4D", "JT4E", "JT4F", "JT4G"],
+    ),
+    ("JT6M", &[]),
+    (
+        "JT9",
+        &[
+            "JT9-1",
+            "JT9-2",
+            "JT9-5",
+            "JT9-10",
+            "JT9-30",
+            "JT9A",
+            "JT9B",
+            "JT9C",
+            "JT9D",
+            "JT9E",
+            "JT9E FAST",
+            "JT9F",
+            "JT9F FAST",
+            "JT9G",
+            "JT9G FAST",
+            "JT9H",
+            "JT9H FAST",
+        ],
+    ),
+    ("JT44", &[]),
+    ("JT65", &["JT65A", "JT65B", "JT65B2", "JT65C", "JT65C2"]),
+    (
+        "MFSK",
+        &[
+            "FSQCALL", "FST4", "FST4W", "FT4", "JS8", "JTMS", "MFSK4", "MFSK8", "MFSK11", "MFSK16",
+            "MFSK22", "MFSK31", "MFSK32", "MFSK64", "MFSK64L", "MFSK128", "MFSK128L", "Q65",
+        ],
+    ),
+    ("MSK144", &[]),
+    ("MT63", &[]),
+    (
+        "OLIVIA",
+        &[
+            "OLIVIA 4/125",
+            "OLIVIA 4/250",
+            "OLIVIA 8/250",
+            "OLIVIA 8/500",
+            "OLIVIA 16/500",
+            "OLIVIA 16/1000",
+            "OLIVIA 32/1000",
+        ],
+    ),
+    ("OPERA", &["OPERA-BEACON", "OPERA-QSO"]),
+    ("PAC", &["PAC2", "PAC3", "PAC4"]),
+    ("PAX", &["PAX2"]),
+    ("PKT", &[]),
+    (
+        "PSK",
+        &[
+            "8PSK125",
+            "8PSK125F",
+            "8PSK125FL",
+            "8PSK250",
+            "8PSK250F",
+            "8PSK250FL",
+            "8PSK500",
+            "8PSK500F",
+            "8PSK1000",
+            "8PSK1000F",
+            "8PSK1200F",
+            "FSK31",
+            "PSK10",
+            "PSK31",
+            "PSK63",
+            "PSK63F",
+            "PSK63RC4",
+            "PSK63RC5",
+            "PSK63RC10",
+            "PSK63RC20",
+            "PSK63RC32",
+            "PSK125",
+            "PSK125C12",
+            "PSK125R",
+            "PSK125RC10",
+            "PSK125RC12",
+            "PSK125RC16",
+            "PSK125RC4",
+            "PSK125RC5",
+            "PSK250",
+            "PSK250C6",
+            "PSK250R",
+            "PSK250RC2",
+            "PSK250RC3",
+            "PSK250RC5",
+            "PSK250RC6",
+            "PSK250RC7",
+            "PSK500",
+            "PSK500C2",
+            "PSK500C4",
+            "PSK500R",
+            "PSK500RC2",
+            "PSK500RC3",
+            "PSK500RC4",
+            "PSK800C2",
+            "PSK800RC2",
+            "PSK1000",
+            "PSK1000C2",
+            "PSK1000R",
+            "PSK1000RC2",
+            "PSKAM10",
+            "PSKAM31",
+            "PSKAM50",
+            "PSKFEC31",
+            "QPSK31",
+            "QPSK63",
+            "QPSK125",
+            "QPSK250",
+            "QPSK500",
+            "SIM31",
+        ],
+    ),
+    ("PSK2K", &[]),
+    ("Q15", &[]),
+    ("QRA64", &["QRA64A", "QRA64B", "QRA64C", "QRA64D", "QRA64E"]),
+    ("ROS", &["ROS-EME", "ROS-HF", "ROS-MF"]),
+    ("RTTY", &["ASCI"]),
+    ("RTTYM", &[]),
+    ("SSB", &["LSB", "USB"]),
+    ("SSTV", &[]),
+    ("T10", &[]),
+    (
+        "THOR",
+        &[
+            "THOR-M", "THOR4", "THOR5", "THOR8", "THOR11", "THOR16", "THOR22", "THOR25X4",
+            "THOR50X1", "THOR50X2", "THOR100",
+        ],
+    ),
+    (
+        "THRB",
+        &[
+            "THRBX", "THRBX1", "THRBX2", "THRBX4", "THROB1", "THROB2", "THROB4",
+        ],
+    ),
+    ("TOR", &["AMTORFEC", "GTOR", "NAVTEX", "SITORB"]),
+    ("V4", &[]),
+    ("VOI", &[]),
+    ("WINMOR", &[]),
+    ("WSPR", &[]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_records() {
+        let content = b"Exported log <ADIF_VER:5>3.1.4 <EOH>\n\
+            <CALL:5>BG1AA <mode:2>CW <EOR>\n\
+            <CALL:6>BY9CLB<FREQ:7:N>438.500<eor>\n";
+        let records = adif_parse(content);
+        assert_eq!(records.len(), 2);
+        let first = records[0].as_ref().unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first["CALL"], "BG1AA");
+        assert_eq!(first["MODE"], "CW");
+        let second = records[1].as_ref().unwrap();
+        assert_eq!(second["CALL"], "BY9CLB");
+        assert_eq!(second["FREQ"], "438.500");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let records = adif_parse(b"<CALL>BG1AA <EOR> <CALL:5>BG1AA <EOR>");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], Err("Field <CALL> has no length".to_string()));
+        assert!(records[1].is_ok());
+
+        let records = adif_parse(b"<CALL:x>BG1AA <EOR>");
+        let error = records[0].as_ref().unwrap_err();
+        assert!(error.starts_with("Invalid length in field <CALL:x>"));
+
+        let records = adif_parse(b"<CALL:5>BG1AA <EOR> <CALL:5>BY9CL");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1], Err("Last record is missing <EOR>".to_string()));
+
+        let records = adif_parse(b"<CALL:9>BG1AA");
+        let error = "Field CALL is longer than the rest of the file";
+        assert_eq!(records, vec![Err(error.to_string())]);
+    }
+
+    #[test]
+    fn generate_line() {
+        assert_eq!(adif_generate_line("CALL", "BG1AA"), "<CALL:5>BG1AA ");
+        assert_eq!(adif_generate_line("NOTES", ""), "<NOTES:0> ");
+    }
+}
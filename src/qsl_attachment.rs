@@ -0,0 +1,60 @@
+use std::fmt::Display;
+use std::path::Path;
+
+/// A file kept with a record, e.g. a card scan or a photo of an eyeball meeting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub(crate) id: i32,
+    /// Id of the record it belongs to
+    pub(crate) qsl_id: i32,
+    /// Name of the file it was attached from, without the folders
+    pub(crate) file_name: String,
+    pub(crate) mime_type: String,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Attachment {
+    /// Name of the copy in the HTML output folder, unique by attachment id.
+    pub fn output_name(&self) -> String {
+        let name = self
+            .file_name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect::<String>();
+        format!("{}_{name}", self.id)
+    }
+}
+
+impl Display for Attachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}, {} KiB)",
+            self.file_name,
+            self.mime_type,
+            self.data.len().div_ceil(1024)
+        )
+    }
+}
+
+/// MIME type guessed from the file extension.
+pub fn mime_type_of(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,225 @@
+use crate::qsl_frequency::get_band;
+use crate::qsl_type::{Mode, QSL};
+use std::io::{BufRead, Write};
+
+/// Header of a Cabrillo 3.0 log, the part contest robots check before the QSO: lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CabrilloHeader {
+    pub(crate) contest: String,
+    pub(crate) callsign: String,
+    pub(crate) category_operator: String,
+    pub(crate) category_assisted: String,
+    pub(crate) category_band: String,
+    pub(crate) category_mode: String,
+    pub(crate) category_power: String,
+    pub(crate) category_transmitter: String,
+    pub(crate) claimed_score: Option<u32>,
+    pub(crate) operators: Option<String>,
+}
+
+impl CabrilloHeader {
+    /// Header of a single operator entry on all bands and modes.
+    pub fn new(contest: &str, callsign: &str, operator: Option<&str>) -> CabrilloHeader {
+        CabrilloHeader {
+            contest: contest.to_string(),
+            callsign: callsign.to_string(),
+            category_operator: "SINGLE-OP".to_string(),
+            category_assisted: "NON-ASSISTED".to_string(),
+            category_band: "ALL".to_string(),
+            category_mode: "MIXED".to_string(),
+            category_power: "HIGH".to_string(),
+            category_transmitter: "ONE".to_string(),
+            claimed_score: None,
+            operators: Some(operator.unwrap_or(callsign).to_string()),
+        }
+    }
+
+    /// Ask for every field on `output`, an empty answer keeps the value shown in brackets.
+    pub fn prompt(
+        mut self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<CabrilloHeader, String> {
+        let mut ask = |name: &str, value: &str| -> Result<String, String> {
+            write!(output, "{name} [{value}]: ").map_err(|e| format!("{e}"))?;
+            output.flush().map_err(|e| format!("{e}"))?;
+            let mut answer = String::new();
+            input.read_line(&mut answer).map_err(|e| format!("{e}"))?;
+            Ok(match answer.trim() {
+                "" => value.to_string(),
+                answer => answer.to_uppercase(),
+            })
+        };
+
+        self.callsign = ask("CALLSIGN", &self.callsign)?;
+        self.category_operator = ask("CATEGORY-OPERATOR", &self.category_operator)?;
+        self.category_assisted = ask("CATEGORY-ASSISTED", &self.category_assisted)?;
+        self.category_band = ask("CATEGORY-BAND", &self.category_band)?;
+        self.category_mode = ask("CATEGORY-MODE", &self.category_mode)?;
+        self.category_power = ask("CATEGORY-POWER", &self.category_power)?;
+        self.category_transmitter = ask("CATEGORY-TRANSMITTER", &self.category_transmitter)?;
+        let claimed_score = ask(
+            "CLAIMED-SCORE",
+            &self.claimed_score.map_or("".to_string(), |s| s.to_string()),
+        )?;
+        self.claimed_score = match claimed_score.as_str() {
+            "" => None,
+            score => Some(
+                score
+                    .parse::<u32>()
+                    .map_err(|e| format!("Parse CLAIMED-SCORE failed: {e}"))?,
+            ),
+        };
+        let operators = ask("OPERATORS", self.operators.as_deref().unwrap_or_default())?;
+        self.operators = Some(operators).filter(|o| !o.is_empty());
+        Ok(self)
+    }
+
+    pub fn fmt_cabrillo(&self) -> String {
+        let mut str = String::new();
+        let mut line = |tag: &str, value: &str| str.push_str(&format!("{tag}: {value}\n"));
+        line("START-OF-LOG", "3.0");
+        line(
+            "CREATED-BY",
+            &format!("qsl_recorder {}", env!("CARGO_PKG_VERSION")),
+        );
+        line("CONTEST", &self.contest);
+        line("CALLSIGN", &self.callsign);
+        line("CATEGORY-OPERATOR", &self.category_operator);
+        line("CATEGORY-ASSISTED", &self.category_assisted);
+        line("CATEGORY-BAND", &self.category_band);
+        line("CATEGORY-MODE", &self.category_mode);
+        line("CATEGORY-POWER", &self.category_power);
+        line("CATEGORY-TRANSMITTER", &self.category_transmitter);
+        if let Some(score) = self.claimed_score {
+            line("CLAIMED-SCORE", &score.to_string());
+        }
+        if let Some(operators) = &self.operators {
+            line("OPERATORS", operators);
+        }
+        str
+    }
+}
+
+/// One QSO: line, e.g. `QSO: 14025 CW 2025-06-01 0100 BY9CLB 599 001 BG1AA 599 015`.
+pub fn cabrillo_qso_line(qsl: &QSL, my_call: &str) -> Result<String, String> {
+    let freq = match &qsl.freq {
+        Some(freq) => cabrillo_freq(freq.tx)?,
+        None => return Err("Frequency is missing.".to_string()),
+    };
+    let sent = exchange(&qsl.rst_me, qsl.stx, &qsl.stx_string);
+    let rcvd = exchange(&qsl.rst_counterpart, qsl.srx, &qsl.srx_string);
+    let line = format!(
+        "QSO: {:>5} {} {} {:<13} {:<10} {:<13} {}",
+        freq,
+        cabrillo_mode(&qsl.mode)?,
+        qsl.datetime.format("%Y-%m-%d %H%M"),
+        my_call,
+        sent,
+        qsl.call_number,
+        rcvd
+    );
+    Ok(format!("{}\n", line.trim_end()))
+}
+
+/// Report, serial and the rest of one side of the exchange.
+fn exchange(rst: &Option<String>, serial: Option<u32>, string: &Option<String>) -> String {
+    let mut parts = Vec::new();
+    if let Some(rst) = rst {
+        parts.push(rst.clone());
+    }
+    if let Some(serial) = serial {
+        parts.push(format!("{serial:03}"));
+    }
+    if let Some(string) = string {
+        parts.push(string.clone());
+    }
+    parts.join(" ")
+}
+
+/// Frequency in kHz below 30 MHz, the band designator above.
+fn cabrillo_freq(freq_mhz: f64) -> Result<String, String> {
+    if freq_mhz < 30.0 {
+        return Ok(format!("{:.0}", freq_mhz * 1000.0));
+    }
+    let band = match get_band(freq_mhz)? {
+        "6m" => "50",
+        "4m" => "70",
+        "2m" => "144",
+        "1.25m" => "222",
+        "70cm" => "432",
+        "33cm" => "902",
+        "23cm" => "1.2G",
+        "13cm" => "2.3G",
+        "9cm" => "3.4G",
+        "6cm" => "5.7G",
+        "3cm" => "10G",
+        "1.25cm" => "24G",
+        "6mm" => "47G",
+        "4mm" => "75G",
+        "2.5mm" => "123G",
+        "2mm" => "134G",
+        "1mm" => "241G",
+        band => return Err(format!("Band {band} has no Cabrillo designator.")),
+    };
+    Ok(band.to_string())
+}
+
+fn cabrillo_mode(mode: &Mode) -> Result<&'static str, String> {
+    match mode.mode() {
+        "CW" => Ok("CW"),
+        "SSB" | "AM" => Ok("PH"),
+        "FM" => Ok("FM"),
+        "RTTY" => Ok("RY"),
+        "EYEBALL" | "OTHER" => Err(format!("Mode {} cannot be put in a Cabrillo log.", mode)),
+        _ => Ok("DG"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn contest_qsl(freq: Option<&str>) -> QSL {
+        let mut fields = HashMap::from([
+            ("CALL", "bg1aa"),
+            ("MODE", "CW"),
+            ("QSO_DATE", "20250601"),
+            ("TIME_ON", "0100"),
+            ("RST_SENT", "599"),
+            ("STX", "1"),
+            ("RST_RCVD", "599"),
+            ("SRX", "15"),
+        ]);
+        if let Some(freq) = freq {
+            fields.insert("FREQ", freq);
+        }
+        let fields = fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        QSL::from_adif(&fields).unwrap()
+    }
+
+    #[test]
+    fn qso_line() {
+        assert_eq!(
+            cabrillo_qso_line(&contest_qsl(Some("14.025")), "BY9CLB").unwrap(),
+            "QSO: 14025 CW 2025-06-01 0100 BY9CLB        599 001    BG1AA         599 015\n"
+        );
+        assert_eq!(
+            cabrillo_qso_line(&contest_qsl(Some("145.5")), "BY9CLB").unwrap(),
+            "QSO:   144 CW 2025-06-01 0100 BY9CLB        599 001    BG1AA         599 015\n"
+        );
+        assert!(cabrillo_qso_line(&contest_qsl(None), "BY9CLB").is_err());
+    }
+
+    #[test]
+    fn freq() {
+        assert_eq!(cabrillo_freq(7.074), Ok("7074".to_string()));
+        assert_eq!(cabrillo_freq(438.5), Ok("432".to_string()));
+        assert_eq!(cabrillo_freq(122250.0), Ok("123G".to_string()));
+        assert!(cabrillo_freq(31.0).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt::Display;
+
+/// Operating condition written after a callsign.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    /// /P
+    Portable,
+    /// /M
+    Mobile,
+    /// /MM, no DXCC entity
+    MaritimeMobile,
+    /// /AM, no DXCC entity
+    AeronauticalMobile,
+    /// /QRP
+    Qrp,
+}
+
+impl Modifier {
+    fn parse(text: &str) -> Option<Modifier> {
+        match text {
+            "P" => Some(Modifier::Portable),
+            "M" => Some(Modifier::Mobile),
+            "MM" => Some(Modifier::MaritimeMobile),
+            "AM" => Some(Modifier::AeronauticalMobile),
+            "QRP" => Some(Modifier::Qrp),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Modifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Modifier::Portable => "P",
+            Modifier::Mobile => "M",
+            Modifier::MaritimeMobile => "MM",
+            Modifier::AeronauticalMobile => "AM",
+            Modifier::Qrp => "QRP",
+        };
+        write!(f, "{text}")
+    }
+}
+
+/// A callsign split into the station and where it operates from,
+/// e.g. `KH6/W1AW/P` is W1AW portable in Hawaii.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Callsign {
+    /// Prefix of the country operated from, KH6 of KH6/W1AW
+    pub(crate) prefix: Option<String>,
+    /// The station itself, the same in every country and condition
+    pub(crate) base: String,
+    /// Call area written as a suffix, 3 of BY1AA/3
+    pub(crate) area: Option<char>,
+    pub(crate) modifiers: Vec<Modifier>,
+}
+
+impl Callsign {
+    /// Read a callsign in any case. The base call must follow the ITU form of
+    /// a prefix, one digit and a suffix ending in a letter.
+    pub fn parse(text: &str) -> Result<Callsign, String> {
+        let upper = text.trim().to_uppercase();
+        let mut calls = Vec::new();
+        let mut area = None;
+        let mut modifiers = Vec::new();
+        for part in upper.split('/') {
+            if part.is_empty() {
+                return Err(format!("{upper} has an empty part."));
+            }
+            if let Some(modifier) = Modifier::parse(part) {
+                modifiers.push(modifier);
+            } else if part.len() == 1 && part.chars().all(|c| c.is_ascii_digit()) {
+                if area.is_some() {
+                    return Err(format!("{upper} has more than one call area."));
+                }
+                area = part.chars().next();
+            } else {
+                calls.push(part);
+            }
+        }
+
+        let (prefix, base) = match calls[..] {
+            [base] => (None, base),
+            // A bare prefix like BY1 is the override, otherwise the shorter one,
+            // and the one written first when both are as long.
+            [first, second] => {
+                let first_is_prefix = match (is_prefix(first), is_prefix(second)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => first.len() <= second.len(),
+                };
+                if first_is_prefix {
+                    (Some(first), second)
+                } else {
+                    (Some(second), first)
+                }
+            }
+            [] => return Err(format!("{upper} has no callsign.")),
+            _ => return Err(format!("{upper} has more than two callsigns.")),
+        };
+        if !is_base_call(base) {
+            return Err(format!("{base} is not a valid callsign."));
+        }
+        if let Some(prefix) = prefix
+            && !is_prefix(prefix)
+            && !is_base_call(prefix)
+        {
+            return Err(format!("{prefix} is not a valid prefix."));
+        }
+
+        Ok(Callsign {
+            prefix: prefix.map(|p| p.to_string()),
+            base: base.to_string(),
+            area,
+            modifiers,
+        })
+    }
+
+    /// Base call of a logged callsign, the callsign itself in upper case when
+    /// it cannot be parsed, e.g. NOCALL.
+    pub fn base_of(text: &str) -> String {
+        match Callsign::parse(text) {
+            Ok(callsign) => callsign.base,
+            Err(_) => text.trim().to_uppercase(),
+        }
+    }
+
+    /// The part a country file is searched with, none at sea or in the air.
+    pub fn dxcc_prefix(&self) -> Option<String> {
+        if self
+            .modifiers
+            .iter()
+            .any(|m| matches!(m, Modifier::MaritimeMobile | Modifier::AeronauticalMobile))
+        {
+            return None;
+        }
+        if let Some(prefix) = &self.prefix {
+            return Some(prefix.clone());
+        }
+        match (self.area, self.base.rfind(|c: char| c.is_ascii_digit())) {
+            (Some(area), Some(digit)) => Some(format!("{}{}", &self.base[..digit], area)),
+            _ => Some(self.base.clone()),
+        }
+    }
+}
+
+impl Display for Callsign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(prefix) = &self.prefix {
+            write!(f, "{prefix}/")?;
+        }
+        write!(f, "{}", self.base)?;
+        if let Some(area) = self.area {
+            write!(f, "/{area}")?;
+        }
+        for modifier in &self.modifiers {
+            write!(f, "/{modifier}")?;
+        }
+        Ok(())
+    }
+}
+
+/// ITU prefix forms: one or two letters, a digit and a letter, or a letter and a digit.
+fn prefix_forms(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let bytes = text.as_bytes();
+    let one = bytes.first().is_some_and(u8::is_ascii_uppercase);
+    let two = bytes.len() >= 2
+        && bytes[..2].iter().all(u8::is_ascii_alphanumeric)
+        && bytes[..2].iter().any(u8::is_ascii_uppercase);
+    [(one, 1), (two, 2)]
+        .into_iter()
+        .filter(|(valid, _)| *valid)
+        .map(|(_, len)| len)
+}
+
+/// Prefix, a digit, then one to four characters ending in a letter, e.g. BI9CKG or 3D2AG.
+fn is_base_call(call: &str) -> bool {
+    let bytes = call.as_bytes();
+    prefix_forms(call).any(|len| {
+        let rest = &bytes[len..];
+        rest.len() >= 2
+            && rest.len() <= 5
+            && rest[0].is_ascii_digit()
+            && rest[1..].iter().all(u8::is_ascii_alphanumeric)
+            && rest.last().is_some_and(u8::is_ascii_uppercase)
+    })
+}
+
+/// A prefix alone, optionally with its call area, e.g. KH6, BY1 or F.
+fn is_prefix(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    prefix_forms(text).any(|len| match &bytes[len..] {
+        [] => true,
+        [digit] => digit.is_ascii_digit(),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plain_callsign() {
+        let callsign = Callsign::parse(" bg7ijk ").unwrap();
+        assert_eq!(callsign.prefix, None);
+        assert_eq!(callsign.base, "BG7IJK");
+        assert_eq!(callsign.area, None);
+        assert!(callsign.modifiers.is_empty());
+        assert_eq!(callsign.to_string(), "BG7IJK");
+        assert_eq!(callsign.dxcc_prefix().as_deref(), Some("BG7IJK"));
+    }
+
+    #[test]
+    fn parse_prefix_and_modifier() {
+        let callsign = Callsign::parse("kh6/w1aw/p").unwrap();
+        assert_eq!(callsign.prefix.as_deref(), Some("KH6"));
+        assert_eq!(callsign.base, "W1AW");
+        assert_eq!(callsign.modifiers, vec![Modifier::Portable]);
+        assert_eq!(callsign.to_string(), "KH6/W1AW/P");
+        assert_eq!(callsign.dxcc_prefix().as_deref(), Some("KH6"));
+    }
+
+    #[test]
+    fn prefix_is_put_first() {
+        let callsign = Callsign::parse("W1AW/KH6").unwrap();
+        assert_eq!(callsign.prefix.as_deref(), Some("KH6"));
+        assert_eq!(callsign.to_string(), "KH6/W1AW");
+        // Two full callsigns as long as each other, the first is the prefix.
+        let callsign = Callsign::parse("BY1PK/BA4AA").unwrap();
+        assert_eq!(callsign.prefix.as_deref(), Some("BY1PK"));
+        assert_eq!(callsign.base, "BA4AA");
+    }
+
+    #[test]
+    fn call_area_replaces_digit() {
+        let callsign = Callsign::parse("BY1AA/9").unwrap();
+        assert_eq!(callsign.area, Some('9'));
+        assert_eq!(callsign.dxcc_prefix().as_deref(), Some("BY9"));
+        let portable = Callsign::parse("W1AW/6/QRP").unwrap();
+        assert_eq!(portable.dxcc_prefix().as_deref(), Some("W6"));
+    }
+
+    #[test]
+    fn maritime_mobile_has_no_entity() {
+        let callsign = Callsign::parse("BY1AA/MM").unwrap();
+        assert_eq!(callsign.modifiers, vec![Modifier::MaritimeMobile]);
+        assert_eq!(callsign.dxcc_prefix(), None);
+        assert_eq!(Callsign::parse("W1AW/AM").unwrap().dxcc_prefix(), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Callsign::parse("").is_err());
+        assert!(Callsign::parse("BY1AA//P").is_err());
+        assert!(Callsign::parse("NOCALL").is_err());
+        assert!(Callsign::parse("BY1AA/1/2").is_err());
+        assert!(Callsign::parse("KH6/W1AW/BY1AA").is_err());
+        assert!(Callsign::parse("P").is_err());
+    }
+
+    #[test]
+    fn base_of_unparsable_callsign() {
+        assert_eq!(Callsign::base_of("kh6/w1aw/p"), "W1AW");
+        assert_eq!(Callsign::base_of(" nocall "), "NOCALL");
+    }
+}
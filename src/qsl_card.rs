@@ -0,0 +1,266 @@
+use crate::qsl_adif_helper::adif_generate_line;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// ADIF QSL_SENT / QSL_RCVD value, shared by paper, eQSL and LoTW.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QSLStatus {
+    /// Y, sent or received
+    Yes,
+    /// N, not sent or not received
+    No,
+    /// R, the counterpart asked for a card, or I asked for theirs
+    Requested,
+    /// Q, in the pile waiting to go out
+    Queued,
+    /// I, will not be sent or the card is invalid
+    Ignore,
+}
+
+impl QSLStatus {
+    pub const ALL: [QSLStatus; 5] = [
+        QSLStatus::Yes,
+        QSLStatus::No,
+        QSLStatus::Requested,
+        QSLStatus::Queued,
+        QSLStatus::Ignore,
+    ];
+
+    pub fn adif(&self) -> &'static str {
+        match self {
+            QSLStatus::Yes => "Y",
+            QSLStatus::No => "N",
+            QSLStatus::Requested => "R",
+            QSLStatus::Queued => "Q",
+            QSLStatus::Ignore => "I",
+        }
+    }
+
+    /// Read an ADIF status letter. The deprecated V (verified) counts as received.
+    pub fn from_adif(text: &str) -> Result<QSLStatus, String> {
+        match text.trim().to_uppercase().as_str() {
+            "Y" | "V" => Ok(QSLStatus::Yes),
+            "N" => Ok(QSLStatus::No),
+            "R" => Ok(QSLStatus::Requested),
+            "Q" => Ok(QSLStatus::Queued),
+            "I" => Ok(QSLStatus::Ignore),
+            _ => Err(format!("{text} is not a QSL status, use Y, N, R, Q or I.")),
+        }
+    }
+}
+
+impl Display for QSLStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            QSLStatus::Yes => "Yes",
+            QSLStatus::No => "No",
+            QSLStatus::Requested => "Requested",
+            QSLStatus::Queued => "Queued",
+            QSLStatus::Ignore => "Ignore",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// How a paper card travels, ADIF QSL_SENT_VIA / QSL_RCVD_VIA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QSLRoute {
+    Bureau,
+    Direct,
+    Electronic,
+    Manager,
+}
+
+impl QSLRoute {
+    pub const ALL: [QSLRoute; 4] = [
+        QSLRoute::Bureau,
+        QSLRoute::Direct,
+        QSLRoute::Electronic,
+        QSLRoute::Manager,
+    ];
+
+    pub fn adif(&self) -> &'static str {
+        match self {
+            QSLRoute::Bureau => "B",
+            QSLRoute::Direct => "D",
+            QSLRoute::Electronic => "E",
+            QSLRoute::Manager => "M",
+        }
+    }
+
+    pub fn from_adif(text: &str) -> Result<QSLRoute, String> {
+        match text.trim().to_uppercase().as_str() {
+            "B" => Ok(QSLRoute::Bureau),
+            "D" => Ok(QSLRoute::Direct),
+            "E" => Ok(QSLRoute::Electronic),
+            "M" => Ok(QSLRoute::Manager),
+            _ => Err(format!("{text} is not a QSL route, use B, D, E or M.")),
+        }
+    }
+}
+
+impl Display for QSLRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            QSLRoute::Bureau => "Bureau",
+            QSLRoute::Direct => "Direct",
+            QSLRoute::Electronic => "Electronic",
+            QSLRoute::Manager => "Manager",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The ways a contact gets confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QSLService {
+    Paper,
+    Eqsl,
+    LoTW,
+}
+
+impl QSLService {
+    pub const ALL: [QSLService; 3] = [QSLService::Paper, QSLService::Eqsl, QSLService::LoTW];
+
+    /// Prefix of the `_sent`, `_sent_date`, `_rcvd` and `_rcvd_date` columns.
+    pub fn column_prefix(&self) -> &'static str {
+        match self {
+            QSLService::Paper => "qsl",
+            QSLService::Eqsl => "eqsl",
+            QSLService::LoTW => "lotw",
+        }
+    }
+
+    /// ADIF fields of sent status, sent date, received status and received date.
+    fn adif_fields(&self) -> [&'static str; 4] {
+        match self {
+            QSLService::Paper => ["QSL_SENT", "QSLSDATE", "QSL_RCVD", "QSLRDATE"],
+            QSLService::Eqsl => [
+                "EQSL_QSL_SENT",
+                "EQSL_QSLSDATE",
+                "EQSL_QSL_RCVD",
+                "EQSL_QSLRDATE",
+            ],
+            QSLService::LoTW => [
+                "LOTW_QSL_SENT",
+                "LOTW_QSLSDATE",
+                "LOTW_QSL_RCVD",
+                "LOTW_QSLRDATE",
+            ],
+        }
+    }
+}
+
+impl Display for QSLService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            QSLService::Paper => "Paper",
+            QSLService::Eqsl => "eQSL",
+            QSLService::LoTW => "LoTW",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Sent and received state of one confirmation service.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QSLCard {
+    pub(crate) sent: Option<QSLStatus>,
+    pub(crate) sent_date: Option<NaiveDate>,
+    pub(crate) received: Option<QSLStatus>,
+    pub(crate) received_date: Option<NaiveDate>,
+}
+
+impl QSLCard {
+    /// The counterpart is waiting for my card: they asked for it, it is queued,
+    /// or theirs arrived and mine has not gone out.
+    pub fn is_owed(&self) -> bool {
+        match self.sent {
+            Some(QSLStatus::Requested | QSLStatus::Queued) => true,
+            Some(QSLStatus::Yes | QSLStatus::Ignore) => false,
+            _ => self.received == Some(QSLStatus::Yes),
+        }
+    }
+
+    /// My card went out and theirs has not come back.
+    pub fn is_awaited(&self) -> bool {
+        self.sent == Some(QSLStatus::Yes)
+            && !matches!(self.received, Some(QSLStatus::Yes | QSLStatus::Ignore))
+    }
+
+    /// Short state for the logs, e.g. "sent 2025-01-02, received".
+    pub fn info(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(sent) = self.sent {
+            parts.push(match (sent, self.sent_date) {
+                (QSLStatus::Yes, Some(date)) => format!("sent {date}"),
+                (QSLStatus::Yes, None) => "sent".to_string(),
+                (status, _) => format!("sent: {status}"),
+            });
+        }
+        if let Some(received) = self.received {
+            parts.push(match (received, self.received_date) {
+                (QSLStatus::Yes, Some(date)) => format!("received {date}"),
+                (QSLStatus::Yes, None) => "received".to_string(),
+                (status, _) => format!("received: {status}"),
+            });
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
+
+    pub fn from_adif(
+        fields: &HashMap<String, String>,
+        service: QSLService,
+    ) -> Result<QSLCard, String> {
+        let [sent, sent_date, received, received_date] = service.adif_fields();
+        let get = |key: &str| fields.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+        let get_status = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(s) => QSLStatus::from_adif(s)
+                .map(Some)
+                .map_err(|e| format!("Parse {key} failed: {e}")),
+        };
+        let get_date = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(d) => NaiveDate::parse_from_str(d, "%Y%m%d")
+                .map(Some)
+                .map_err(|e| format!("Parse {key} {d} failed: {e}")),
+        };
+
+        Ok(QSLCard {
+            sent: get_status(sent)?,
+            sent_date: get_date(sent_date)?,
+            received: get_status(received)?,
+            received_date: get_date(received_date)?,
+        })
+    }
+
+    pub fn fmt_adif(&self, service: QSLService) -> String {
+        let [sent, sent_date, received, received_date] = service.adif_fields();
+        let mut str = String::new();
+        if let Some(status) = self.sent {
+            str.push_str(&adif_generate_line(sent, status.adif()));
+        }
+        if let Some(date) = self.sent_date {
+            str.push_str(&adif_generate_line(
+                sent_date,
+                &date.format("%Y%m%d").to_string(),
+            ));
+        }
+        if let Some(status) = self.received {
+            str.push_str(&adif_generate_line(received, status.adif()));
+        }
+        if let Some(date) = self.received_date {
+            str.push_str(&adif_generate_line(
+                received_date,
+                &date.format("%Y%m%d").to_string(),
+            ));
+        }
+        str
+    }
+}
@@ -1,8 +1,20 @@
-use crate::qsl_type::{Mode, QSL};
+use crate::qsl_attachment::Attachment;
+use crate::qsl_callsign::Callsign;
+use crate::qsl_card::{QSLCard, QSLRoute, QSLService, QSLStatus};
+use crate::qsl_country::Country;
+use crate::qsl_frequency::{Frequency, Tone, band_range};
+use crate::qsl_history::{FieldChange, HistoryAction, HistoryEntry};
+use crate::qsl_migration::migrate;
+use crate::qsl_station::{EquipmentPreset, StationProfile};
+use crate::qsl_type::{
+    Contact, Mode, QSL, QSLFilter, QSLSearch, RecordKind, TimeSpan, parse_time_zone,
+};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta, Utc};
 use cursive::reexports::log;
-use rusqlite::{Connection, Error, Row, ToSql, params};
+use rusqlite::types::{Type, Value};
+use rusqlite::{Connection, Error, Row, Transaction, params, params_from_iter};
 
-const NEW_DATABASE_QUERY: &str = r#"
+pub const NEW_DATABASE_QUERY: &str = r#"
 BEGIN;
 
 CREATE TABLE qsl (
@@ -33,12 +45,69 @@ COMMIT;
 "#;
 const UPDATE_SETTING_QUERY: &str = "INSERT INTO setting(call_number) VALUES (?1);";
 const READ_SETTING_QUERY: &str = "SELECT call_number FROM setting";
+const READ_ACTIVE_PROFILE_QUERY: &str = "SELECT active_profile FROM setting";
+const UPDATE_ACTIVE_PROFILE_QUERY: &str = "UPDATE setting SET active_profile = ?1";
+const GET_STATION_PROFILES_QUERY: &str = "SELECT * FROM station_profile ORDER BY id";
+const GET_STATION_PROFILE_QUERY: &str = "SELECT * FROM station_profile WHERE id = ?1";
+const ADD_STATION_PROFILE_QUERY: &str = r#"
+INSERT INTO station_profile (callsign, operator, qth, grid, rig, ant, watt)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+"#;
+const UPDATE_STATION_PROFILE_QUERY: &str = r#"
+UPDATE station_profile
+SET callsign = ?1, operator = ?2, qth = ?3, grid = ?4, rig = ?5, ant = ?6, watt = ?7
+WHERE id = ?8
+"#;
+const DELETE_STATION_PROFILE_QUERY: &str = "DELETE FROM station_profile WHERE id = ?1";
+const COUNT_STATION_PROFILE_USE_QUERY: &str = "SELECT COUNT(*) FROM qsl WHERE station_profile = ?1";
+const GET_EQUIPMENT_PRESETS_QUERY: &str = "SELECT * FROM equipment_preset ORDER BY name";
+const ADD_EQUIPMENT_PRESET_QUERY: &str =
+    "INSERT INTO equipment_preset (name, qth, rig, ant, watt) VALUES (?1, ?2, ?3, ?4, ?5)";
+const UPDATE_EQUIPMENT_PRESET_QUERY: &str =
+    "UPDATE equipment_preset SET name = ?1, qth = ?2, rig = ?3, ant = ?4, watt = ?5 WHERE id = ?6";
+const DELETE_EQUIPMENT_PRESET_QUERY: &str = "DELETE FROM equipment_preset WHERE id = ?1";
+const READ_PREFILL_FROM_LAST_QUERY: &str = "SELECT prefill_from_last FROM setting";
+const UPDATE_PREFILL_FROM_LAST_QUERY: &str = "UPDATE setting SET prefill_from_last = ?1";
+const READ_TRASH_RETENTION_QUERY: &str = "SELECT trash_retention_days FROM setting";
+const UPDATE_TRASH_RETENTION_QUERY: &str = "UPDATE setting SET trash_retention_days = ?1";
+const NEXT_SERIAL_QUERY: &str =
+    "SELECT COALESCE(MAX(stx), 0) + 1 FROM live_qsl WHERE contest_id = ?1";
+const READ_COUNTRY_FILE_QUERY: &str = "SELECT country_file FROM setting";
+const UPDATE_COUNTRY_FILE_QUERY: &str = "UPDATE setting SET country_file = ?1";
+const GET_ALL_ID_AND_CALL_QUERY: &str = "SELECT id, call_number FROM qsl";
+const UPDATE_COUNTRY_QUERY: &str =
+    "UPDATE qsl SET dxcc = ?1, country = ?2, cont = ?3, cqz = ?4, ituz = ?5 WHERE id = ?6";
+const GET_CONTACT_QUERY: &str =
+    "SELECT name, email, address, qsl_via FROM contact WHERE base_call = ?1";
+const REMEMBER_CONTACT_QUERY: &str = r#"
+INSERT INTO contact (base_call, name, email, address, qsl_via)
+VALUES (?1, ?2, ?3, ?4, ?5)
+ON CONFLICT(base_call) DO UPDATE SET
+    name = COALESCE(excluded.name, name),
+    email = COALESCE(excluded.email, email),
+    address = COALESCE(excluded.address, address),
+    qsl_via = COALESCE(excluded.qsl_via, qsl_via)
+"#;
+const GET_STATES_QUERY: &str =
+    "SELECT call_number, state, dxcc FROM formal_qsl WHERE state IS NOT NULL AND {condition}";
+const GET_BY_BASE_CALL_QUERY: &str =
+    "SELECT * FROM live_qsl WHERE base_call = ?1 ORDER BY datetime";
+const COUNT_STATIONS_QUERY: &str = "SELECT COUNT(DISTINCT base_call) FROM formal_qsl";
+const GET_LAST_QSL_QUERY: &str = "SELECT * FROM live_qsl ORDER BY id DESC LIMIT 1";
+const READ_TIME_ZONE_QUERY: &str = "SELECT time_zone FROM setting";
+const UPDATE_TIME_ZONE_QUERY: &str = "UPDATE setting SET time_zone = ?1";
+const READ_DATETIMES_CONVERTED_QUERY: &str = "SELECT datetimes_converted FROM setting";
+const UPDATE_DATETIMES_CONVERTED_QUERY: &str = "UPDATE setting SET datetimes_converted = 1";
+const SHIFT_DATETIME_QUERY: &str =
+    "UPDATE qsl SET datetime = datetime(datetime, ?1), datetime_off = datetime(datetime_off, ?1)";
+/// Datetimes are stored as UTC text in this format, which SQLite's date functions understand.
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 const CHECK_EXISTENCE_QUERY: &str = "SELECT 1 FROM qsl WHERE id = ?1";
 const ADD_ELEMENT_QUERY: &str = r#"
 INSERT INTO qsl (
     call_number,
-    call_type,
-    freq,
+    mode,
+    freq_tx,
     datetime,
     rst_me,
     rig_me,
@@ -50,22 +119,128 @@ INSERT INTO qsl (
     watt_counterpart,
     ant_counterpart,
     qth_counterpart,
-    note
+    note,
+    sat_name,
+    sat_mode,
+    freq_rx,
+    tone,
+    grid_me,
+    grid_counterpart,
+    lat_me,
+    lon_me,
+    lat_counterpart,
+    lon_counterpart,
+    submode,
+    datetime_off,
+    qsl_sent,
+    qsl_sent_date,
+    qsl_rcvd,
+    qsl_rcvd_date,
+    eqsl_sent,
+    eqsl_sent_date,
+    eqsl_rcvd,
+    eqsl_rcvd_date,
+    lotw_sent,
+    lotw_sent_date,
+    lotw_rcvd,
+    lotw_rcvd_date,
+    qsl_sent_via,
+    qsl_rcvd_via,
+    qsl_via,
+    station_profile,
+    contest_id,
+    stx,
+    srx,
+    stx_string,
+    srx_string,
+    dxcc,
+    country,
+    cont,
+    cqz,
+    ituz,
+    base_call,
+    state,
+    name_counterpart,
+    email_counterpart,
+    address_counterpart
 )
-VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37, ?38, ?39, ?40, ?41, ?42, ?43, ?44, ?45, ?46, ?47, ?48, ?49, ?50, ?51, ?52, ?53, ?54, ?55, ?56, ?57, ?58)
 "#;
 const UPDATE_ELEMENT_QUERY: &str = r#"
 UPDATE qsl
-SET call_number = ?1, call_type = ?2, freq = ?3, datetime = ?4, rst_me = ?5, rig_me = ?6, watt_me = ?7, ant_me = ?8, qth_me = ?9, rst_counterpart = ?10, rig_counterpart = ?11, watt_counterpart = ?12, ant_counterpart = ?13, qth_counterpart = ?14, note = ?15
-WHERE id = ?16
+SET call_number = ?1, mode = ?2, freq_tx = ?3, datetime = ?4, rst_me = ?5, rig_me = ?6, watt_me = ?7, ant_me = ?8, qth_me = ?9, rst_counterpart = ?10, rig_counterpart = ?11, watt_counterpart = ?12, ant_counterpart = ?13, qth_counterpart = ?14, note = ?15, sat_name = ?16, sat_mode = ?17, freq_rx = ?18, tone = ?19, grid_me = ?20, grid_counterpart = ?21, lat_me = ?22, lon_me = ?23, lat_counterpart = ?24, lon_counterpart = ?25, submode = ?26, datetime_off = ?27, qsl_sent = ?28, qsl_sent_date = ?29, qsl_rcvd = ?30, qsl_rcvd_date = ?31, eqsl_sent = ?32, eqsl_sent_date = ?33, eqsl_rcvd = ?34, eqsl_rcvd_date = ?35, lotw_sent = ?36, lotw_sent_date = ?37, lotw_rcvd = ?38, lotw_rcvd_date = ?39, qsl_sent_via = ?40, qsl_rcvd_via = ?41, qsl_via = ?42, station_profile = ?43, contest_id = ?44, stx = ?45, srx = ?46, stx_string = ?47, srx_string = ?48, dxcc = ?49, country = ?50, cont = ?51, cqz = ?52, ituz = ?53, base_call = ?54, state = ?55, name_counterpart = ?56, email_counterpart = ?57, address_counterpart = ?58
+WHERE id = ?59
 "#;
 const DELETE_ELEMENT_QUERY: &str = "DELETE FROM qsl WHERE id = ?1";
-const GET_QSL_PAGE_QUERY: &str = "SELECT * FROM qsl ORDER BY datetime LIMIT ?1 OFFSET ?2";
-const COUNT_QUERY: &str = "SELECT COUNT(*) FROM qsl";
-const COUNT_EYEBALL_QUERY: &str = "SELECT COUNT(*) FROM eyeball_qsl";
-const GET_EYEBALL_QUERY: &str = "SELECT * FROM eyeball_qsl ORDER BY datetime LIMIT ?1 OFFSET ?2";
-const COUNT_NON_EYEBALL_QUERY: &str = "SELECT COUNT(*) FROM formal_qsl";
-const GET_NON_EYEBALL_QUERY: &str = "SELECT * FROM formal_qsl ORDER BY datetime LIMIT ?1 OFFSET ?2";
+const TRASH_QUERY: &str = "UPDATE qsl SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL";
+const RESTORE_QUERY: &str =
+    "UPDATE qsl SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL";
+const IS_TRASHED_QUERY: &str = "SELECT deleted_at IS NOT NULL FROM qsl WHERE id = ?1";
+const GET_TRASH_QUERY: &str =
+    "SELECT * FROM qsl WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC";
+const GET_ATTACHMENTS_QUERY: &str = "SELECT * FROM attachment WHERE qsl_id = ?1 ORDER BY id";
+const GET_IMAGE_ATTACHMENTS_QUERY: &str = r#"
+SELECT attachment.* FROM attachment
+JOIN (SELECT id FROM live_qsl WHERE {condition}) AS shown ON shown.id = attachment.qsl_id
+WHERE attachment.mime_type LIKE 'image/%'
+ORDER BY attachment.id
+"#;
+const ADD_ATTACHMENT_QUERY: &str =
+    "INSERT INTO attachment (qsl_id, file_name, mime_type, data) VALUES (?1, ?2, ?3, ?4)";
+const DELETE_ATTACHMENT_QUERY: &str = "DELETE FROM attachment WHERE id = ?1";
+const DELETE_ATTACHMENTS_OF_QSL_QUERY: &str = "DELETE FROM attachment WHERE qsl_id = ?1";
+const GET_QSL_PAGE_QUERY: &str =
+    "SELECT * FROM live_qsl WHERE {condition} ORDER BY datetime LIMIT ?1 OFFSET ?2";
+const COUNT_QUERY: &str = "SELECT COUNT(*) FROM live_qsl WHERE {condition}";
+const COUNT_EYEBALL_QUERY: &str = "SELECT COUNT(*) FROM eyeball_qsl WHERE {condition}";
+const GET_EYEBALL_QUERY: &str =
+    "SELECT * FROM eyeball_qsl WHERE {condition} ORDER BY datetime LIMIT ?1 OFFSET ?2";
+const COUNT_NON_EYEBALL_QUERY: &str = "SELECT COUNT(*) FROM formal_qsl WHERE {condition}";
+const GET_NON_EYEBALL_QUERY: &str =
+    "SELECT * FROM formal_qsl WHERE {condition} ORDER BY datetime LIMIT ?1 OFFSET ?2";
+const GET_CONTEST_QUERY: &str = r#"
+SELECT * FROM formal_qsl
+WHERE contest_id = ?1 AND (?2 IS NULL OR datetime >= ?2) AND (?3 IS NULL OR datetime < ?3)
+  AND {condition}
+ORDER BY datetime
+"#;
+const QSL_COLUMNS_QUERY: &str = "SELECT name FROM pragma_table_info('qsl') ORDER BY cid";
+const ADD_HISTORY_QUERY: &str = r#"
+INSERT INTO qsl_history (qsl_id, action, changed_at, station_profile, old_record, new_record)
+VALUES (?1, ?2, ?3, (SELECT active_profile FROM setting), ?4, ?5)
+"#;
+const GET_HISTORY_QUERY: &str = r#"
+SELECT qsl_history.*, station_profile.callsign FROM qsl_history
+LEFT JOIN station_profile ON station_profile.id = qsl_history.station_profile
+WHERE ?1 IS NULL OR qsl_id = ?1
+ORDER BY qsl_history.id DESC
+LIMIT 500
+"#;
+const HISTORY_CHANGES_QUERY: &str = r#"
+SELECT key, old_value, new_value FROM (
+    SELECT keys.key,
+        (SELECT CAST(value AS TEXT) FROM json_each(?1) WHERE key = keys.key) AS old_value,
+        (SELECT CAST(value AS TEXT) FROM json_each(?2) WHERE key = keys.key) AS new_value
+    FROM (SELECT key FROM json_each(?1) UNION SELECT key FROM json_each(?2)) AS keys
+)
+WHERE old_value IS NOT new_value
+"#;
+const GET_ALL_TAGS_QUERY: &str = "SELECT name FROM tag ORDER BY name";
+const GET_TAGS_QUERY: &str = r#"
+SELECT tag.name FROM qsl_tag JOIN tag ON tag.id = qsl_tag.tag_id
+WHERE qsl_tag.qsl_id = ?1 ORDER BY tag.name
+"#;
+const ADD_TAG_QUERY: &str = "INSERT OR IGNORE INTO tag (name) VALUES (?1)";
+const ADD_QSL_TAG_QUERY: &str =
+    "INSERT OR IGNORE INTO qsl_tag (qsl_id, tag_id) SELECT ?1, id FROM tag WHERE name = ?2";
+const DELETE_QSL_TAGS_QUERY: &str = "DELETE FROM qsl_tag WHERE qsl_id = ?1";
+const DELETE_UNUSED_TAGS_QUERY: &str =
+    "DELETE FROM tag WHERE id NOT IN (SELECT tag_id FROM qsl_tag)";
+const GET_TIME_SPANS_QUERY: &str = r#"
+SELECT datetime, datetime_off FROM formal_qsl
+WHERE datetime_off IS NOT NULL AND {condition}
+ORDER BY datetime
+"#;
 
 pub struct QSLContext {
     database: Connection,
@@ -79,7 +254,8 @@ impl QSLContext {
         }
 
         match Connection::open(db_file_path) {
-            Ok(connection) => {
+            Ok(mut connection) => {
+                let mut time_zone = None;
                 if !path_is_exist {
                     println!("Enter your name (\"NOCALL\"): ");
                     let mut call_sign = String::new();
@@ -96,6 +272,19 @@ impl QSLContext {
                         }
                     }
 
+                    println!("Enter the time zone you log in (\"+08:00\"): ");
+                    let mut input = String::new();
+                    match std::io::stdin().read_line(&mut input) {
+                        Ok(_) => {
+                            let input = input.trim();
+                            let input = if input.is_empty() { "+08:00" } else { input };
+                            time_zone = Some(parse_time_zone(input)?);
+                        }
+                        Err(e) => {
+                            return Err(format!("Cannot read time_zone value: {}", e));
+                        }
+                    }
+
                     match connection.execute_batch(NEW_DATABASE_QUERY) {
                         Ok(_) => match connection.execute(UPDATE_SETTING_QUERY, params![call_sign])
                         {
@@ -116,9 +305,14 @@ impl QSLContext {
                         }
                     }
                 }
-                Ok(QSLContext {
+                migrate(&mut connection)?;
+                let context = QSLContext {
                     database: connection,
-                })
+                };
+                if let Some(time_zone) = time_zone {
+                    context.set_time_zone(&time_zone)?;
+                }
+                Ok(context)
             }
             Err(whatever) => Err(format!(
                 "Failed to open the database with the following error: {}.",
@@ -148,14 +342,584 @@ impl QSLContext {
         }
     }
 
-    pub fn add_qsl(&self, new_qsl: QSL) -> Result<(), String> {
+    pub fn get_active_profile_id(&self) -> Result<i32, String> {
+        match self
+            .database
+            .query_row(READ_ACTIVE_PROFILE_QUERY, [], |row| row.get(0))
+        {
+            Ok(id) => Ok(id),
+            Err(e) => Err(format!("Failed to get active station profile: {}", e)),
+        }
+    }
+
+    pub fn set_active_profile_id(&self, id: i32) -> Result<(), String> {
+        match self
+            .database
+            .execute(UPDATE_ACTIVE_PROFILE_QUERY, params![id])
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to set active station profile: {}", e)),
+        }
+    }
+
+    fn parse_row_to_profile(row: &Row) -> Result<StationProfile, Error> {
+        Ok(StationProfile {
+            id: row.get("id")?,
+            callsign: row.get("callsign")?,
+            operator: row.get("operator")?,
+            qth: row.get("qth")?,
+            grid: row.get("grid")?,
+            rig: row.get("rig")?,
+            ant: row.get("ant")?,
+            watt: row.get("watt")?,
+        })
+    }
+
+    pub fn get_station_profiles(&self) -> Result<Vec<StationProfile>, String> {
+        let mut stmt = self
+            .database
+            .prepare(GET_STATION_PROFILES_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map([], Self::parse_row_to_profile)
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    pub fn get_station_profile(&self, id: i32) -> Result<StationProfile, String> {
+        match self.database.query_row(
+            GET_STATION_PROFILE_QUERY,
+            params![id],
+            Self::parse_row_to_profile,
+        ) {
+            Ok(profile) => Ok(profile),
+            Err(e) => Err(format!("Failed to get station profile {}: {}", id, e)),
+        }
+    }
+
+    /// Add a profile and return its id.
+    pub fn add_station_profile(&self, profile: &StationProfile) -> Result<i32, String> {
+        match self.database.execute(
+            ADD_STATION_PROFILE_QUERY,
+            params![
+                &profile.callsign,
+                profile.operator.as_deref(),
+                profile.qth.as_deref(),
+                profile.grid.as_deref(),
+                profile.rig.as_deref(),
+                profile.ant.as_deref(),
+                profile.watt
+            ],
+        ) {
+            Ok(_) => Ok(self.database.last_insert_rowid() as i32),
+            Err(e) => Err(format!("Failed to add station profile: {}", e)),
+        }
+    }
+
+    pub fn update_station_profile(&self, profile: &StationProfile) -> Result<(), String> {
+        match self.database.execute(
+            UPDATE_STATION_PROFILE_QUERY,
+            params![
+                &profile.callsign,
+                profile.operator.as_deref(),
+                profile.qth.as_deref(),
+                profile.grid.as_deref(),
+                profile.rig.as_deref(),
+                profile.ant.as_deref(),
+                profile.watt,
+                profile.id
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to update station profile: {}", e)),
+        }
+    }
+
+    /// Delete a profile no record refers to.
+    pub fn delete_station_profile(&self, id: i32) -> Result<(), String> {
+        let used =
+            match self
+                .database
+                .query_row(COUNT_STATION_PROFILE_USE_QUERY, params![id], |row| {
+                    row.get::<_, i64>(0)
+                }) {
+                Ok(count) => count,
+                Err(e) => return Err(format!("Failed to check station profile {}: {}", id, e)),
+            };
+        if used > 0 {
+            return Err(format!(
+                "Station profile {} is used by {} record(s).",
+                id, used
+            ));
+        }
+
+        match self
+            .database
+            .execute(DELETE_STATION_PROFILE_QUERY, params![id])
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to delete station profile: {}", e)),
+        }
+    }
+
+    pub fn get_equipment_presets(&self) -> Result<Vec<EquipmentPreset>, String> {
+        let mut stmt = self
+            .database
+            .prepare(GET_EQUIPMENT_PRESETS_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(EquipmentPreset {
+                    id: row.get("id")?,
+                    name: row.get("name")?,
+                    qth: row.get("qth")?,
+                    rig: row.get("rig")?,
+                    ant: row.get("ant")?,
+                    watt: row.get("watt")?,
+                })
+            })
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    pub fn add_equipment_preset(&self, preset: &EquipmentPreset) -> Result<(), String> {
+        match self.database.execute(
+            ADD_EQUIPMENT_PRESET_QUERY,
+            params![
+                &preset.name,
+                preset.qth.as_deref(),
+                preset.rig.as_deref(),
+                preset.ant.as_deref(),
+                preset.watt
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to add equipment preset: {}", e)),
+        }
+    }
+
+    pub fn update_equipment_preset(&self, preset: &EquipmentPreset) -> Result<(), String> {
+        match self.database.execute(
+            UPDATE_EQUIPMENT_PRESET_QUERY,
+            params![
+                &preset.name,
+                preset.qth.as_deref(),
+                preset.rig.as_deref(),
+                preset.ant.as_deref(),
+                preset.watt,
+                preset.id
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to update equipment preset: {}", e)),
+        }
+    }
+
+    pub fn delete_equipment_preset(&self, id: i32) -> Result<(), String> {
+        match self
+            .database
+            .execute(DELETE_EQUIPMENT_PRESET_QUERY, params![id])
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to delete equipment preset: {}", e)),
+        }
+    }
+
+    /// Every tag in use, by name.
+    pub fn get_all_tags(&self) -> Result<Vec<String>, String> {
+        self.query_tags(GET_ALL_TAGS_QUERY, [])
+    }
+
+    pub fn get_tags(&self, qsl_id: i32) -> Result<Vec<String>, String> {
+        self.query_tags(GET_TAGS_QUERY, params![qsl_id])
+    }
+
+    fn query_tags(
+        &self,
+        query: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<String>, String> {
+        let mut stmt = self
+            .database
+            .prepare(query)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params, |row| row.get(0))
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// Replace the tags of a record. Tags no record uses any more are dropped.
+    pub fn set_tags(&self, qsl_id: i32, tags: &[String]) -> Result<(), String> {
+        let result = self
+            .database
+            .execute(DELETE_QSL_TAGS_QUERY, params![qsl_id])
+            .and_then(|_| {
+                for tag in tags {
+                    self.database.execute(ADD_TAG_QUERY, params![tag])?;
+                    self.database
+                        .execute(ADD_QSL_TAG_QUERY, params![qsl_id, tag])?;
+                }
+                self.database.execute(DELETE_UNUSED_TAGS_QUERY, [])
+            });
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to set tags of {}: {}", qsl_id, e)),
+        }
+    }
+
+    pub fn get_attachments(&self, qsl_id: i32) -> Result<Vec<Attachment>, String> {
+        self.query_attachments(GET_ATTACHMENTS_QUERY, params![qsl_id])
+    }
+
+    /// Images of the records that match `filter` and are not in the trash, for the HTML output.
+    pub fn get_image_attachments(&self, filter: &QSLFilter) -> Result<Vec<Attachment>, String> {
+        let mut values = Vec::new();
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = GET_IMAGE_ATTACHMENTS_QUERY.replace("{condition}", &condition);
+        self.query_attachments(&query, params_from_iter(values))
+    }
+
+    fn query_attachments(
+        &self,
+        query: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<Attachment>, String> {
+        let mut stmt = self
+            .database
+            .prepare(query)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params, |row| {
+                Ok(Attachment {
+                    id: row.get("id")?,
+                    qsl_id: row.get("qsl_id")?,
+                    file_name: row.get("file_name")?,
+                    mime_type: row.get("mime_type")?,
+                    data: row.get("data")?,
+                })
+            })
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    pub fn add_attachment(&self, attachment: &Attachment) -> Result<(), String> {
+        match self.database.execute(
+            ADD_ATTACHMENT_QUERY,
+            params![
+                attachment.qsl_id,
+                &attachment.file_name,
+                &attachment.mime_type,
+                &attachment.data
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to add attachment: {}", e)),
+        }
+    }
+
+    pub fn delete_attachment(&self, id: i32) -> Result<(), String> {
+        match self.database.execute(DELETE_ATTACHMENT_QUERY, params![id]) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to delete attachment: {}", e)),
+        }
+    }
+
+    /// Whether new records start with the equipment of the last logged contact.
+    pub fn get_prefill_from_last(&self) -> Result<bool, String> {
+        match self
+            .database
+            .query_row(READ_PREFILL_FROM_LAST_QUERY, [], |row| row.get(0))
+        {
+            Ok(prefill) => Ok(prefill),
+            Err(e) => Err(format!("Failed to get prefill setting: {}", e)),
+        }
+    }
+
+    pub fn set_prefill_from_last(&self, prefill: bool) -> Result<(), String> {
+        match self
+            .database
+            .execute(UPDATE_PREFILL_FROM_LAST_QUERY, params![prefill])
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to set prefill setting: {}", e)),
+        }
+    }
+
+    /// The record added most recently, which is not always the latest by datetime.
+    pub fn get_last_qsl(&self) -> Result<Option<QSL>, String> {
+        match self
+            .database
+            .query_row(GET_LAST_QSL_QUERY, [], Self::parse_row_to_qsl)
+        {
+            Ok(qsl) => Ok(Some(qsl)),
+            Err(Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(format!("Failed to get the last QSL record: {}", e)),
+        }
+    }
+
+    /// Serial number to send next in a contest, one after the highest sent so far.
+    /// Records in the trash are left out, so their serials are sent again.
+    pub fn get_next_serial(&self, contest_id: &str) -> Result<u32, String> {
+        match self
+            .database
+            .query_row(NEXT_SERIAL_QUERY, params![contest_id], |row| row.get(0))
+        {
+            Ok(serial) => Ok(serial),
+            Err(e) => Err(format!("Failed to get the next serial number: {}", e)),
+        }
+    }
+
+    /// Every contact with a station, whatever prefix or suffix it used.
+    pub fn get_qsl_by_base_call(&self, base_call: &str) -> Result<Vec<QSL>, String> {
+        let mut stmt = self
+            .database
+            .prepare(GET_BY_BASE_CALL_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params![base_call], Self::parse_row_to_qsl)
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// Contact details remembered for a base call, if any were logged.
+    pub fn get_contact(&self, base_call: &str) -> Result<Option<Contact>, String> {
+        match self
+            .database
+            .query_row(GET_CONTACT_QUERY, params![base_call], |row| {
+                Ok(Contact {
+                    name: row.get(0)?,
+                    email: row.get(1)?,
+                    address: row.get(2)?,
+                    qsl_via: row.get(3)?,
+                })
+            }) {
+            Ok(contact) => Ok(Some(contact)),
+            Err(Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(format!("Failed to get contact of {base_call}: {}", e)),
+        }
+    }
+
+    /// Keep the contact details of a record for its base call. Details the
+    /// record leaves empty keep what was remembered before.
+    fn remember_contact(&self, qsl: &QSL) -> Result<(), String> {
+        if qsl.name_counterpart.is_none()
+            && qsl.email_counterpart.is_none()
+            && qsl.address_counterpart.is_none()
+            && qsl.qsl_via.is_none()
+        {
+            return Ok(());
+        }
+        match self.database.execute(
+            REMEMBER_CONTACT_QUERY,
+            params![
+                Callsign::base_of(&qsl.call_number),
+                qsl.name_counterpart.as_deref(),
+                qsl.email_counterpart.as_deref(),
+                qsl.address_counterpart.as_deref(),
+                qsl.qsl_via.as_deref()
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to remember contact: {}", e)),
+        }
+    }
+
+    /// Callsign, STATE and DXCC of every on-air record with a STATE that matches `filter`.
+    pub fn get_states(
+        &self,
+        filter: &QSLFilter,
+    ) -> Result<Vec<(String, String, Option<u32>)>, String> {
+        let mut values = Vec::new();
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = GET_STATES_QUERY.replace("{condition}", &condition);
+        let mut stmt = self
+            .database
+            .prepare(&query)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params_from_iter(values), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// Number of different stations worked, counted by base call.
+    pub fn get_station_count(&self) -> Result<i64, String> {
+        match self
+            .database
+            .query_row(COUNT_STATIONS_QUERY, [], |row| row.get(0))
+        {
+            Ok(count) => Ok(count),
+            Err(e) => Err(format!("Failed to get station count: {}", e)),
+        }
+    }
+
+    /// Path of the country file, if one was chosen.
+    pub fn get_country_file(&self) -> Result<Option<String>, String> {
+        match self
+            .database
+            .query_row(READ_COUNTRY_FILE_QUERY, [], |row| row.get(0))
+        {
+            Ok(path) => Ok(path),
+            Err(e) => Err(format!("Failed to get country file: {}", e)),
+        }
+    }
+
+    pub fn set_country_file(&self, path: &str) -> Result<(), String> {
+        match self
+            .database
+            .execute(UPDATE_COUNTRY_FILE_QUERY, params![path])
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to set country file: {}", e)),
+        }
+    }
+
+    /// Id and callsign of every record, eyeball ones included.
+    pub fn get_all_callsigns(&self) -> Result<Vec<(i32, String)>, String> {
+        let mut stmt = self
+            .database
+            .prepare(GET_ALL_ID_AND_CALL_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    pub fn update_country(&self, id: i32, country: Option<&Country>) -> Result<(), String> {
+        match self.database.execute(
+            UPDATE_COUNTRY_QUERY,
+            params![
+                country.and_then(|c| c.dxcc),
+                country.map(|c| c.name.as_str()),
+                country.map(|c| c.continent.as_str()),
+                country.map(|c| c.cq_zone),
+                country.map(|c| c.itu_zone),
+                id
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to update country of record {id}: {}", e)),
+        }
+    }
+
+    pub fn get_time_zone(&self) -> Result<FixedOffset, String> {
+        match self
+            .database
+            .query_row(READ_TIME_ZONE_QUERY, [], |row| row.get::<_, String>(0))
+        {
+            Ok(time_zone) => parse_time_zone(&time_zone),
+            Err(e) => Err(format!("Failed to get time zone: {}", e)),
+        }
+    }
+
+    pub fn set_time_zone(&self, time_zone: &FixedOffset) -> Result<(), String> {
+        match self
+            .database
+            .execute(UPDATE_TIME_ZONE_QUERY, params![time_zone.to_string()])
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to set time zone: {}", e)),
+        }
+    }
+
+    /// Whether the stored datetimes were already converted to UTC.
+    pub fn get_datetimes_converted(&self) -> Result<bool, String> {
+        match self
+            .database
+            .query_row(READ_DATETIMES_CONVERTED_QUERY, [], |row| row.get(0))
+        {
+            Ok(converted) => Ok(converted),
+            Err(e) => Err(format!("Failed to read datetime conversion: {}", e)),
+        }
+    }
+
+    /// Treat every stored datetime as local time in `time_zone` and convert it to UTC.
+    /// For logbooks that were filled in local time before datetimes were UTC, so it
+    /// is refused once it has been done.
+    pub fn convert_datetimes_to_utc(&self, time_zone: &FixedOffset) -> Result<usize, String> {
+        let modifier = format!("{} minutes", -time_zone.local_minus_utc() / 60);
+        let result = self
+            .database
+            .unchecked_transaction()
+            .and_then(|transaction| {
+                if transaction.query_row(READ_DATETIMES_CONVERTED_QUERY, [], |row| row.get(0))? {
+                    return Ok(None);
+                }
+                let count = transaction.execute(SHIFT_DATETIME_QUERY, params![modifier])?;
+                transaction.execute(UPDATE_DATETIMES_CONVERTED_QUERY, [])?;
+                transaction.commit()?;
+                Ok(Some(count))
+            });
+        match result {
+            Ok(Some(count)) => Ok(count),
+            Ok(None) => Err("Datetimes were already converted to UTC.".to_string()),
+            Err(e) => Err(format!("Failed to convert datetimes: {}", e)),
+        }
+    }
+
+    /// Begin a transaction for a change made of several writes, so they are
+    /// kept together or not at all. Dropping it without `commit` rolls them back.
+    fn begin(&self) -> Result<Transaction<'_>, String> {
+        self.database
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))
+    }
+
+    fn commit(transaction: Transaction) -> Result<(), String> {
+        transaction
+            .commit()
+            .map_err(|e| format!("Failed to commit transaction: {}", e))
+    }
+
+    /// Add a record and return its id.
+    pub fn add_qsl(&self, new_qsl: QSL) -> Result<i32, String> {
+        let transaction = self.begin()?;
         match &self.database.execute(
             ADD_ELEMENT_QUERY,
             params![
                 &new_qsl.call_number,
-                new_qsl.mode as i32,
-                new_qsl.freq.as_deref(),
-                &new_qsl.datetime.to_sql().unwrap(),
+                new_qsl.mode.mode(),
+                new_qsl.freq.as_ref().map(|f| f.tx),
+                &new_qsl.datetime.format(DATETIME_FORMAT).to_string(),
                 new_qsl.rst_me.as_deref(),
                 new_qsl.rig_me.as_deref(),
                 new_qsl.watt_me,
@@ -166,10 +930,64 @@ impl QSLContext {
                 new_qsl.watt_counterpart,
                 new_qsl.ant_counterpart.as_deref(),
                 new_qsl.qth_counterpart.as_deref(),
-                new_qsl.note.as_deref()
+                new_qsl.note.as_deref(),
+                new_qsl.sat_name.as_deref(),
+                new_qsl.sat_mode.as_deref(),
+                new_qsl.freq.as_ref().and_then(|f| f.rx),
+                new_qsl
+                    .freq
+                    .as_ref()
+                    .and_then(|f| f.tone.as_ref().map(|t| t.to_string())),
+                new_qsl.grid_me.as_deref(),
+                new_qsl.grid_counterpart.as_deref(),
+                new_qsl.lat_me,
+                new_qsl.lon_me,
+                new_qsl.lat_counterpart,
+                new_qsl.lon_counterpart,
+                new_qsl.mode.submode(),
+                new_qsl
+                    .datetime_off
+                    .map(|d| d.format(DATETIME_FORMAT).to_string()),
+                new_qsl.card_paper.sent.map(|s| s.adif()),
+                new_qsl.card_paper.sent_date.map(|d| d.to_string()),
+                new_qsl.card_paper.received.map(|s| s.adif()),
+                new_qsl.card_paper.received_date.map(|d| d.to_string()),
+                new_qsl.card_eqsl.sent.map(|s| s.adif()),
+                new_qsl.card_eqsl.sent_date.map(|d| d.to_string()),
+                new_qsl.card_eqsl.received.map(|s| s.adif()),
+                new_qsl.card_eqsl.received_date.map(|d| d.to_string()),
+                new_qsl.card_lotw.sent.map(|s| s.adif()),
+                new_qsl.card_lotw.sent_date.map(|d| d.to_string()),
+                new_qsl.card_lotw.received.map(|s| s.adif()),
+                new_qsl.card_lotw.received_date.map(|d| d.to_string()),
+                new_qsl.qsl_sent_via.map(|r| r.adif()),
+                new_qsl.qsl_rcvd_via.map(|r| r.adif()),
+                new_qsl.qsl_via.as_deref(),
+                new_qsl.station_profile,
+                new_qsl.contest_id.as_deref(),
+                new_qsl.stx,
+                new_qsl.srx,
+                new_qsl.stx_string.as_deref(),
+                new_qsl.srx_string.as_deref(),
+                new_qsl.dxcc,
+                new_qsl.country.as_deref(),
+                new_qsl.cont.as_deref(),
+                new_qsl.cqz,
+                new_qsl.ituz,
+                Callsign::base_of(&new_qsl.call_number),
+                new_qsl.state.as_deref(),
+                new_qsl.name_counterpart.as_deref(),
+                new_qsl.email_counterpart.as_deref(),
+                new_qsl.address_counterpart.as_deref()
             ],
         ) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                let id = self.database.last_insert_rowid() as i32;
+                self.remember_contact(&new_qsl)?;
+                self.record_history(HistoryAction::Insert, id, None)?;
+                Self::commit(transaction)?;
+                Ok(id)
+            }
             Err(e) => Err(format!("{}", e)),
         }
     }
@@ -190,14 +1008,17 @@ impl QSLContext {
             }
         }
 
+        let transaction = self.begin()?;
+        let old_record = self.snapshot(updated_qsl.id)?;
+
         // Update the entry
         match self.database.execute(
             UPDATE_ELEMENT_QUERY,
             params![
                 &updated_qsl.call_number,
-                updated_qsl.mode as i32,
-                updated_qsl.freq.as_deref(),
-                &updated_qsl.datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+                updated_qsl.mode.mode(),
+                updated_qsl.freq.as_ref().map(|f| f.tx),
+                &updated_qsl.datetime.format(DATETIME_FORMAT).to_string(),
                 updated_qsl.rst_me.as_deref(),
                 updated_qsl.rig_me.as_deref(),
                 updated_qsl.watt_me,
@@ -209,74 +1030,569 @@ impl QSLContext {
                 updated_qsl.ant_counterpart.as_deref(),
                 updated_qsl.qth_counterpart.as_deref(),
                 updated_qsl.note.as_deref(),
+                updated_qsl.sat_name.as_deref(),
+                updated_qsl.sat_mode.as_deref(),
+                updated_qsl.freq.as_ref().and_then(|f| f.rx),
+                updated_qsl
+                    .freq
+                    .as_ref()
+                    .and_then(|f| f.tone.as_ref().map(|t| t.to_string())),
+                updated_qsl.grid_me.as_deref(),
+                updated_qsl.grid_counterpart.as_deref(),
+                updated_qsl.lat_me,
+                updated_qsl.lon_me,
+                updated_qsl.lat_counterpart,
+                updated_qsl.lon_counterpart,
+                updated_qsl.mode.submode(),
+                updated_qsl
+                    .datetime_off
+                    .map(|d| d.format(DATETIME_FORMAT).to_string()),
+                updated_qsl.card_paper.sent.map(|s| s.adif()),
+                updated_qsl.card_paper.sent_date.map(|d| d.to_string()),
+                updated_qsl.card_paper.received.map(|s| s.adif()),
+                updated_qsl.card_paper.received_date.map(|d| d.to_string()),
+                updated_qsl.card_eqsl.sent.map(|s| s.adif()),
+                updated_qsl.card_eqsl.sent_date.map(|d| d.to_string()),
+                updated_qsl.card_eqsl.received.map(|s| s.adif()),
+                updated_qsl.card_eqsl.received_date.map(|d| d.to_string()),
+                updated_qsl.card_lotw.sent.map(|s| s.adif()),
+                updated_qsl.card_lotw.sent_date.map(|d| d.to_string()),
+                updated_qsl.card_lotw.received.map(|s| s.adif()),
+                updated_qsl.card_lotw.received_date.map(|d| d.to_string()),
+                updated_qsl.qsl_sent_via.map(|r| r.adif()),
+                updated_qsl.qsl_rcvd_via.map(|r| r.adif()),
+                updated_qsl.qsl_via.as_deref(),
+                updated_qsl.station_profile,
+                updated_qsl.contest_id.as_deref(),
+                updated_qsl.stx,
+                updated_qsl.srx,
+                updated_qsl.stx_string.as_deref(),
+                updated_qsl.srx_string.as_deref(),
+                updated_qsl.dxcc,
+                updated_qsl.country.as_deref(),
+                updated_qsl.cont.as_deref(),
+                updated_qsl.cqz,
+                updated_qsl.ituz,
+                Callsign::base_of(&updated_qsl.call_number),
+                updated_qsl.state.as_deref(),
+                updated_qsl.name_counterpart.as_deref(),
+                updated_qsl.email_counterpart.as_deref(),
+                updated_qsl.address_counterpart.as_deref(),
                 updated_qsl.id
             ],
         ) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.remember_contact(&updated_qsl)?;
+                self.record_history(HistoryAction::Update, updated_qsl.id, old_record.as_deref())?;
+                Self::commit(transaction)
+            }
             Err(e) => Err(format!("Failed to update QSL record: {}", e)),
         }
     }
 
+    /// Move a record to the trash. It is left out of the table, counts and
+    /// exports until it is restored, and keeps its tags and attachments.
     pub fn delete(&self, id: i32) -> Result<(), String> {
-        // Check if the entry with the given ID exists
-        match self.id_is_exist(id) {
-            Ok(result) => {
-                if !result {
-                    return Err(format!("QSL with ID {} does not exist.", id));
-                }
+        let transaction = self.begin()?;
+        let old_record = self.snapshot(id)?;
+        match self.database.execute(
+            TRASH_QUERY,
+            params![id, Utc::now().format(DATETIME_FORMAT).to_string()],
+        ) {
+            Ok(0) => Err(format!("QSL with ID {} is not in the logbook.", id)),
+            Ok(_) => {
+                self.record_history(HistoryAction::Delete, id, old_record.as_deref())?;
+                Self::commit(transaction)
             }
-            Err(e) => {
-                return Err(format!(
-                    "Could not check id {} exists. Error info {}.",
-                    id, e
-                ));
+            Err(e) => Err(format!("Failed to move QSL record {} to trash: {}", id, e)),
+        }
+    }
+
+    /// Take a record out of the trash.
+    pub fn restore(&self, id: i32) -> Result<(), String> {
+        let transaction = self.begin()?;
+        let old_record = self.snapshot(id)?;
+        match self.database.execute(RESTORE_QUERY, params![id]) {
+            Ok(0) => Err(format!("QSL with ID {} is not in the trash.", id)),
+            Ok(_) => {
+                self.record_history(HistoryAction::Restore, id, old_record.as_deref())?;
+                Self::commit(transaction)
             }
+            Err(e) => Err(format!("Failed to restore QSL record {}: {}", id, e)),
+        }
+    }
+
+    /// Remove a trashed record for good, with its tags and attachments.
+    /// The history still has the record, so it can be reverted back into the trash.
+    pub fn purge(&self, id: i32) -> Result<(), String> {
+        let transaction = self.begin()?;
+        match self
+            .database
+            .query_row(IS_TRASHED_QUERY, params![id], |row| row.get(0))
+        {
+            Ok(true) => {}
+            Ok(false) | Err(Error::QueryReturnedNoRows) => {
+                return Err(format!("QSL with ID {} is not in the trash.", id));
+            }
+            Err(e) => return Err(format!("Could not check id {} is trashed: {}", id, e)),
+        }
+
+        if let Err(e) = self
+            .database
+            .execute(DELETE_ATTACHMENTS_OF_QSL_QUERY, params![id])
+        {
+            return Err(format!("Failed to delete attachments of {}: {}", id, e));
         }
+        self.set_tags(id, &[])?;
+        let old_record = self.snapshot(id)?;
 
         // Delete the entry
         match self.database.execute(DELETE_ELEMENT_QUERY, params![id]) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.record_history(HistoryAction::Purge, id, old_record.as_deref())?;
+                Self::commit(transaction)
+            }
             Err(e) => Err(format!("Failed to delete QSL record: {}", e)),
         }
     }
 
+    /// Trashed records with the time they were deleted, latest first.
+    pub fn get_trash(&self) -> Result<Vec<(QSL, DateTime<Utc>)>, String> {
+        let mut stmt = self
+            .database
+            .prepare(GET_TRASH_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    Self::parse_row_to_qsl(row)?,
+                    row.get::<_, NaiveDateTime>("deleted_at")?.and_utc(),
+                ))
+            })
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// Purge the records trashed before `cutoff`, returns how many.
+    pub fn purge_trash_before(&self, cutoff: DateTime<Utc>) -> Result<usize, String> {
+        let expired = self
+            .get_trash()?
+            .into_iter()
+            .filter(|(_, deleted_at)| *deleted_at < cutoff)
+            .map(|(qsl, _)| qsl.id)
+            .collect::<Vec<_>>();
+        for id in &expired {
+            self.purge(*id)?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Days trashed records are kept before they are purged, none to keep them.
+    pub fn get_trash_retention(&self) -> Result<Option<u32>, String> {
+        match self
+            .database
+            .query_row(READ_TRASH_RETENTION_QUERY, [], |row| row.get(0))
+        {
+            Ok(days) => Ok(days),
+            Err(e) => Err(format!("Failed to get trash retention: {}", e)),
+        }
+    }
+
+    pub fn set_trash_retention(&self, days: Option<u32>) -> Result<(), String> {
+        match self
+            .database
+            .execute(UPDATE_TRASH_RETENTION_QUERY, params![days])
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to set trash retention: {}", e)),
+        }
+    }
+
+    /// Names of the columns of the qsl table, including those added by migrations.
+    fn qsl_columns(&self) -> Result<Vec<String>, String> {
+        let mut stmt = self
+            .database
+            .prepare(QSL_COLUMNS_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// The whole record as a JSON object of its columns, none when it does not exist.
+    pub fn snapshot(&self, id: i32) -> Result<Option<String>, String> {
+        // SQL functions take a limited number of arguments, so the columns
+        // are put in by parts. json_insert keeps null values, unlike json_patch.
+        let object = self
+            .qsl_columns()?
+            .chunks(40)
+            .map(|columns| {
+                columns
+                    .iter()
+                    .map(|c| format!("'$.{c}', \"{c}\""))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .fold("'{}'".to_string(), |object, part| {
+                format!("json_insert({object}, {part})")
+            });
+        let query = format!("SELECT {object} FROM qsl WHERE id = ?1");
+        match self
+            .database
+            .query_row(&query, params![id], |row| row.get(0))
+        {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(format!("Failed to read record {}: {}", id, e)),
+        }
+    }
+
+    /// Keep a change of record `qsl_id` with the profile that is active now.
+    /// The record after the change is read back from the table.
+    fn record_history(
+        &self,
+        action: HistoryAction,
+        qsl_id: i32,
+        old_record: Option<&str>,
+    ) -> Result<(), String> {
+        let new_record = self.snapshot(qsl_id)?;
+        match self.database.execute(
+            ADD_HISTORY_QUERY,
+            params![
+                qsl_id,
+                action.as_str(),
+                Utc::now().format(DATETIME_FORMAT).to_string(),
+                old_record,
+                new_record
+            ],
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Failed to record history of {}: {}", qsl_id, e)),
+        }
+    }
+
+    /// Changes of one record, or of every record when `qsl_id` is none, latest first.
+    pub fn get_history(&self, qsl_id: Option<i32>) -> Result<Vec<HistoryEntry>, String> {
+        let mut stmt = self
+            .database
+            .prepare(GET_HISTORY_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params![qsl_id], |row| {
+                let action = row.get::<_, String>("action")?;
+                Ok(HistoryEntry {
+                    id: row.get("id")?,
+                    qsl_id: row.get("qsl_id")?,
+                    action: HistoryAction::parse(&action)
+                        .map_err(|e| Error::FromSqlConversionFailure(2, Type::Text, e.into()))?,
+                    changed_at: row.get::<_, NaiveDateTime>("changed_at")?.and_utc(),
+                    profile: row.get("callsign")?,
+                    old_record: row.get("old_record")?,
+                    new_record: row.get("new_record")?,
+                })
+            })
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// Columns a change touched, with their values before and after.
+    pub fn get_history_changes(&self, entry: &HistoryEntry) -> Result<Vec<FieldChange>, String> {
+        let mut stmt = self
+            .database
+            .prepare(HISTORY_CHANGES_QUERY)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params![entry.old_record, entry.new_record], |row| {
+                Ok(FieldChange {
+                    field: row.get("key")?,
+                    old_value: row.get("old_value")?,
+                    new_value: row.get("new_value")?,
+                })
+            })
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// Put a record back to how it was before `entry`, restoring it if it was deleted.
+    pub fn revert(&self, entry: &HistoryEntry) -> Result<(), String> {
+        match &entry.old_record {
+            Some(old_record) => {
+                let transaction = self.begin()?;
+                self.write_snapshot(entry.qsl_id, old_record)?;
+                Self::commit(transaction)
+            }
+            None => Err(format!("Record {} did not exist before.", entry.qsl_id)),
+        }
+    }
+
+    /// Put a record back to a snapshot of it with the tags it had then, as one change.
+    pub fn revert_to(&self, qsl_id: i32, record: &str, tags: &[String]) -> Result<(), String> {
+        let transaction = self.begin()?;
+        self.write_snapshot(qsl_id, record)?;
+        self.set_tags(qsl_id, tags)?;
+        Self::commit(transaction)
+    }
+
+    /// Write a snapshot of a record back, inserting it again if it was purged.
+    fn write_snapshot(&self, qsl_id: i32, record: &str) -> Result<(), String> {
+        let current = self.snapshot(qsl_id)?;
+        // Columns the old version has, the id stays as it is.
+        let columns = self
+            .qsl_columns()?
+            .into_iter()
+            .filter(|c| c != "id")
+            .collect::<Vec<_>>();
+        let values = columns
+            .iter()
+            .map(|c| format!("json_extract(?1, '$.{c}')"))
+            .collect::<Vec<_>>();
+        let query = match current {
+            Some(_) => format!(
+                "UPDATE qsl SET {} WHERE id = ?2",
+                columns
+                    .iter()
+                    .zip(&values)
+                    .map(|(c, v)| format!("\"{c}\" = {v}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            None => format!(
+                "INSERT INTO qsl (id, {}) VALUES (?2, {})",
+                columns
+                    .iter()
+                    .map(|c| format!("\"{c}\""))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                values.join(", ")
+            ),
+        };
+        if let Err(e) = self.database.execute(&query, params![record, qsl_id]) {
+            return Err(format!("Failed to revert record {}: {}", qsl_id, e));
+        }
+        self.record_history(HistoryAction::Revert, qsl_id, current.as_deref())
+    }
+
     fn parse_row_to_qsl(row: &Row) -> Result<QSL, Error> {
+        let freq = match row.get::<_, Option<f64>>("freq_tx")? {
+            Some(tx) => Some(Frequency {
+                tx,
+                rx: row.get("freq_rx")?,
+                tone: match row.get::<_, Option<String>>("tone")? {
+                    Some(tone) => Tone::parse(&tone).ok(),
+                    None => None,
+                },
+            }),
+            None => None,
+        };
+        let mode = row.get::<_, String>("mode")?;
+        let submode = row.get::<_, Option<String>>("submode")?;
         Ok(QSL {
-            id: row.get(0)?,
-            call_number: row.get(1)?,
-            mode: match row.get::<_, i32>(2)? {
-                0 => Mode::EYEBALL,
-                1 => Mode::FM,
-                2 => Mode::SSB,
-                3 => Mode::CW,
-                4 => Mode::FTB,
-                _ => Mode::OTHER,
+            id: row.get("id")?,
+            call_number: row.get("call_number")?,
+            mode: Mode::new(&mode, submode.as_deref()).unwrap_or_else(|e| {
+                log::warn!("Context::parse_row_to_qsl: {e} Read as OTHER.");
+                Mode::OTHER
+            }),
+            freq,
+            datetime: row.get::<_, NaiveDateTime>("datetime")?.and_utc(),
+            datetime_off: row
+                .get::<_, Option<NaiveDateTime>>("datetime_off")?
+                .map(|d| d.and_utc()),
+            card_paper: Self::parse_row_to_card(row, QSLService::Paper)?,
+            card_eqsl: Self::parse_row_to_card(row, QSLService::Eqsl)?,
+            card_lotw: Self::parse_row_to_card(row, QSLService::LoTW)?,
+            qsl_sent_via: match row.get::<_, Option<String>>("qsl_sent_via")? {
+                Some(route) => QSLRoute::from_adif(&route).ok(),
+                None => None,
+            },
+            qsl_rcvd_via: match row.get::<_, Option<String>>("qsl_rcvd_via")? {
+                Some(route) => QSLRoute::from_adif(&route).ok(),
+                None => None,
             },
-            freq: row.get(3)?,
-            datetime: row.get(4)?,
-            rst_me: row.get(5)?,
-            rig_me: row.get(6)?,
-            watt_me: row.get(7)?,
-            ant_me: row.get(8)?,
-            qth_me: row.get(9)?,
-            rst_counterpart: row.get(10)?,
-            rig_counterpart: row.get(11)?,
-            watt_counterpart: row.get(12)?,
-            ant_counterpart: row.get(13)?,
-            qth_counterpart: row.get(14)?,
-            note: row.get(15)?,
+            qsl_via: row.get("qsl_via")?,
+            station_profile: row.get("station_profile")?,
+            contest_id: row.get("contest_id")?,
+            stx: row.get("stx")?,
+            srx: row.get("srx")?,
+            stx_string: row.get("stx_string")?,
+            srx_string: row.get("srx_string")?,
+            dxcc: row.get("dxcc")?,
+            country: row.get("country")?,
+            cont: row.get("cont")?,
+            cqz: row.get("cqz")?,
+            ituz: row.get("ituz")?,
+            state: row.get("state")?,
+            rst_me: row.get("rst_me")?,
+            rig_me: row.get("rig_me")?,
+            watt_me: row.get("watt_me")?,
+            ant_me: row.get("ant_me")?,
+            qth_me: row.get("qth_me")?,
+            rst_counterpart: row.get("rst_counterpart")?,
+            rig_counterpart: row.get("rig_counterpart")?,
+            watt_counterpart: row.get("watt_counterpart")?,
+            ant_counterpart: row.get("ant_counterpart")?,
+            name_counterpart: row.get("name_counterpart")?,
+            email_counterpart: row.get("email_counterpart")?,
+            address_counterpart: row.get("address_counterpart")?,
+            qth_counterpart: row.get("qth_counterpart")?,
+            note: row.get("note")?,
+            sat_name: row.get("sat_name")?,
+            sat_mode: row.get("sat_mode")?,
+            grid_me: row.get("grid_me")?,
+            grid_counterpart: row.get("grid_counterpart")?,
+            lat_me: row.get("lat_me")?,
+            lon_me: row.get("lon_me")?,
+            lat_counterpart: row.get("lat_counterpart")?,
+            lon_counterpart: row.get("lon_counterpart")?,
         })
     }
 
-    pub fn get_qsl_page(&self, page_size: i64, page_number: i64) -> Result<Vec<QSL>, String> {
+    fn parse_row_to_card(row: &Row, service: QSLService) -> Result<QSLCard, Error> {
+        let prefix = service.column_prefix();
+        let status = |column: String| -> Result<Option<QSLStatus>, Error> {
+            Ok(match row.get::<_, Option<String>>(column.as_str())? {
+                Some(status) => QSLStatus::from_adif(&status).ok(),
+                None => None,
+            })
+        };
+        Ok(QSLCard {
+            sent: status(format!("{prefix}_sent"))?,
+            sent_date: row.get(format!("{prefix}_sent_date").as_str())?,
+            received: status(format!("{prefix}_rcvd"))?,
+            received_date: row.get(format!("{prefix}_rcvd_date").as_str())?,
+        })
+    }
+
+    /// Binds `value` after the parameters already in `values` and returns its placeholder.
+    fn bind(values: &mut Vec<Value>, value: impl Into<Value>) -> String {
+        values.push(value.into());
+        format!("?{}", values.len())
+    }
+
+    /// SQL condition of a filter, the same rules as `QSLCard::is_owed` and `QSLCard::is_awaited`.
+    /// User input is bound as parameters appended to `values`.
+    fn filter_condition(filter: &QSLFilter, values: &mut Vec<Value>) -> String {
+        let card_condition = |condition: fn(&str) -> String| {
+            QSLService::ALL
+                .map(|service| condition(service.column_prefix()))
+                .join(" OR ")
+        };
+        match filter {
+            QSLFilter::All => "1".to_string(),
+            QSLFilter::CardsOwed => card_condition(|p| {
+                format!(
+                    "({p}_sent IN ('R', 'Q') OR ({p}_rcvd = 'Y' AND COALESCE({p}_sent, 'N') NOT IN ('Y', 'I')))"
+                )
+            }),
+            QSLFilter::CardsAwaited => card_condition(|p| {
+                format!("({p}_sent = 'Y' AND COALESCE({p}_rcvd, 'N') NOT IN ('Y', 'I'))")
+            }),
+            QSLFilter::Callsign(base_call) => {
+                format!("base_call = {}", Self::bind(values, base_call.clone()))
+            }
+            QSLFilter::Tag(tag) => format!(
+                "id IN (SELECT qsl_id FROM qsl_tag JOIN tag ON tag.id = qsl_tag.tag_id WHERE tag.name = {})",
+                Self::bind(values, tag.clone())
+            ),
+            QSLFilter::Search(search) => Self::search_condition(search, values),
+        }
+    }
+
+    /// Condition of the search view. Text is matched with LIKE, so its
+    /// wildcards are escaped, and the band becomes a range of `freq_tx`.
+    fn search_condition(search: &QSLSearch, values: &mut Vec<Value>) -> String {
+        let like = |text: &str| {
+            text.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        };
+        let datetime = |d: DateTime<FixedOffset>| d.to_utc().format(DATETIME_FORMAT).to_string();
+
+        let mut conditions = vec!["1".to_string()];
+        if let Some(callsign) = &search.callsign {
+            let pattern = match search.callsign_prefix {
+                true => format!("{}%", like(callsign)),
+                false => format!("%{}%", like(callsign)),
+            };
+            conditions.push(format!(
+                "call_number LIKE {} ESCAPE '\\'",
+                Self::bind(values, pattern)
+            ));
+        }
+        if let Some(mode) = &search.mode {
+            conditions.push(format!("mode = {}", Self::bind(values, mode.clone())));
+        }
+        if let Some(band) = &search.band {
+            conditions.push(match band_range(band) {
+                Some((lower, upper)) => format!(
+                    "freq_tx BETWEEN {} AND {}",
+                    Self::bind(values, lower),
+                    Self::bind(values, upper)
+                ),
+                None => "0".to_string(),
+            });
+        }
+        if let Some(from) = search.from {
+            let from = datetime(from);
+            conditions.push(format!("datetime >= {}", Self::bind(values, from)));
+        }
+        if let Some(to) = search.to {
+            let until = datetime(to + TimeDelta::days(1));
+            conditions.push(format!("datetime < {}", Self::bind(values, until)));
+        }
+        match search.kind {
+            RecordKind::All => {}
+            RecordKind::Eyeball => conditions.push("mode = 'EYEBALL'".to_string()),
+            RecordKind::OnAir => conditions.push("mode != 'EYEBALL'".to_string()),
+        }
+        if let Some(note) = &search.note {
+            let pattern = format!("%{}%", like(note));
+            conditions.push(format!(
+                "note LIKE {} ESCAPE '\\'",
+                Self::bind(values, pattern)
+            ));
+        }
+        conditions.join(" AND ")
+    }
+
+    pub fn get_qsl_page(
+        &self,
+        filter: &QSLFilter,
+        page_size: i64,
+        page_number: i64,
+    ) -> Result<Vec<QSL>, String> {
         let offset = page_number * page_size;
         log::debug!("Context::get_qsl_page: offset is {offset}");
+        let mut values = vec![Value::from(page_size), Value::from(offset)];
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = GET_QSL_PAGE_QUERY.replace("{condition}", &condition);
         let mut stmt = self
             .database
-            .prepare(GET_QSL_PAGE_QUERY)
+            .prepare(&query)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
         let rows = stmt
-            .query_map(params![page_size, offset], Self::parse_row_to_qsl)
+            .query_map(params_from_iter(values), Self::parse_row_to_qsl)
             .map_err(|e| format!("Failed to query map: {}", e))?;
 
         let mut result = Vec::new();
@@ -288,17 +1604,26 @@ impl QSLContext {
         Ok(result)
     }
 
-    pub fn get_qsl_count(&self) -> Result<i64, String> {
-        match self.database.query_row(COUNT_QUERY, [], |row| row.get(0)) {
+    pub fn get_qsl_count(&self, filter: &QSLFilter) -> Result<i64, String> {
+        let mut values = Vec::new();
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = COUNT_QUERY.replace("{condition}", &condition);
+        match self
+            .database
+            .query_row(&query, params_from_iter(values), |row| row.get(0))
+        {
             Ok(count) => Ok(count),
             Err(e) => Err(format!("Failed to get QSL count: {}", e)),
         }
     }
 
-    pub fn get_eyeball_qsl_count(&self) -> Result<i64, String> {
+    pub fn get_eyeball_qsl_count(&self, filter: &QSLFilter) -> Result<i64, String> {
+        let mut values = Vec::new();
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = COUNT_EYEBALL_QUERY.replace("{condition}", &condition);
         match self
             .database
-            .query_row(COUNT_EYEBALL_QUERY, [], |row| row.get(0))
+            .query_row(&query, params_from_iter(values), |row| row.get(0))
         {
             Ok(count) => Ok(count),
             Err(e) => Err(format!("Failed to get Eyeball QSL count: {}", e)),
@@ -307,17 +1632,21 @@ impl QSLContext {
 
     pub fn get_eyeball_qsl_page(
         &self,
+        filter: &QSLFilter,
         page_size: i64,
         page_number: i64,
     ) -> Result<Vec<QSL>, String> {
         let offset = page_number * page_size;
         log::debug!("Context::get_eyeball_qsl_page: offset is {offset}");
+        let mut values = vec![Value::from(page_size), Value::from(offset)];
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = GET_EYEBALL_QUERY.replace("{condition}", &condition);
         let mut stmt = self
             .database
-            .prepare(GET_EYEBALL_QUERY)
+            .prepare(&query)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
         let rows = stmt
-            .query_map(params![page_size, offset], Self::parse_row_to_qsl)
+            .query_map(params_from_iter(values), Self::parse_row_to_qsl)
             .map_err(|e| format!("Failed to query map: {}", e))?;
 
         let mut result = Vec::new();
@@ -331,10 +1660,13 @@ impl QSLContext {
         );
         Ok(result)
     }
-    pub fn get_formal_qsl_count(&self) -> Result<i64, String> {
+    pub fn get_formal_qsl_count(&self, filter: &QSLFilter) -> Result<i64, String> {
+        let mut values = Vec::new();
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = COUNT_NON_EYEBALL_QUERY.replace("{condition}", &condition);
         match self
             .database
-            .query_row(COUNT_NON_EYEBALL_QUERY, [], |row| row.get(0))
+            .query_row(&query, params_from_iter(values), |row| row.get(0))
         {
             Ok(count) => Ok(count),
             Err(e) => Err(format!("Failed to get Formal QSL count: {}", e)),
@@ -343,17 +1675,21 @@ impl QSLContext {
 
     pub fn get_formal_qsl_page(
         &self,
+        filter: &QSLFilter,
         page_size: i64,
         page_number: i64,
     ) -> Result<Vec<QSL>, String> {
         let offset = page_number * page_size;
         log::debug!("Context::get_formal_qsl_page: offset is {offset}");
+        let mut values = vec![Value::from(page_size), Value::from(offset)];
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = GET_NON_EYEBALL_QUERY.replace("{condition}", &condition);
         let mut stmt = self
             .database
-            .prepare(GET_NON_EYEBALL_QUERY)
+            .prepare(&query)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
         let rows = stmt
-            .query_map(params![page_size, offset], Self::parse_row_to_qsl)
+            .query_map(params_from_iter(values), Self::parse_row_to_qsl)
             .map_err(|e| format!("Failed to query map: {}", e))?;
 
         let mut result = Vec::new();
@@ -367,4 +1703,59 @@ impl QSLContext {
         );
         Ok(result)
     }
+
+    /// Contacts of a contest from `from` up to before `until`, ordered by datetime.
+    pub fn get_contest_qsl(
+        &self,
+        contest_id: &str,
+        from: Option<NaiveDateTime>,
+        until: Option<NaiveDateTime>,
+        filter: &QSLFilter,
+    ) -> Result<Vec<QSL>, String> {
+        let mut values = vec![
+            Value::from(contest_id.to_string()),
+            Value::from(from.map(|d| d.format(DATETIME_FORMAT).to_string())),
+            Value::from(until.map(|d| d.format(DATETIME_FORMAT).to_string())),
+        ];
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = GET_CONTEST_QUERY.replace("{condition}", &condition);
+        let mut stmt = self
+            .database
+            .prepare(&query)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params_from_iter(values), Self::parse_row_to_qsl)
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
+
+    /// Start and end of every contact with an end time that matches `filter`, ordered by start.
+    pub fn get_time_spans(&self, filter: &QSLFilter) -> Result<Vec<TimeSpan>, String> {
+        let mut values = Vec::new();
+        let condition = Self::filter_condition(filter, &mut values);
+        let query = GET_TIME_SPANS_QUERY.replace("{condition}", &condition);
+        let mut stmt = self
+            .database
+            .prepare(&query)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params_from_iter(values), |row| {
+                Ok((
+                    row.get::<_, NaiveDateTime>(0)?.and_utc(),
+                    row.get::<_, NaiveDateTime>(1)?.and_utc(),
+                ))
+            })
+            .map_err(|e| format!("Failed to query map: {}", e))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row.map_err(|e| format!("Failed to read row: {}", e))?);
+        }
+        Ok(result)
+    }
 }
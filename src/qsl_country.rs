@@ -0,0 +1,244 @@
+use crate::qsl_callsign::Callsign;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// DXCC entity a callsign belongs to, with the zones of its prefix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Country {
+    /// ADIF COUNTRY, e.g. China
+    pub(crate) name: String,
+    /// ADIF DXCC entity code, only cty.csv carries it
+    pub(crate) dxcc: Option<u32>,
+    /// ADIF CONT, e.g. AS
+    pub(crate) continent: String,
+    pub(crate) cq_zone: u32,
+    pub(crate) itu_zone: u32,
+}
+
+/// Prefix table of a country file in the cty.dat or cty.csv format of
+/// https://www.country-files.com. Entities marked with `*` only count for
+/// the WAE list and are left out, so every result is a DXCC entity.
+pub struct CountryFile {
+    /// Number of entities read
+    entities: usize,
+    prefixes: HashMap<String, Country>,
+    /// Callsigns listed with `=`, which do not follow their prefix
+    exact: HashMap<String, Country>,
+}
+
+impl CountryFile {
+    /// Read a country file, cty.csv when the name ends with .csv, cty.dat otherwise.
+    pub fn load(path: &Path) -> Result<CountryFile, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let is_csv = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        if is_csv {
+            CountryFile::parse_csv(&text)
+        } else {
+            CountryFile::parse_dat(&text)
+        }
+    }
+
+    /// `Name: CQ: ITU: Cont: Lat: Lon: TZ: Prefix:` followed by aliases up to `;`.
+    pub fn parse_dat(text: &str) -> Result<CountryFile, String> {
+        let mut file = CountryFile::new();
+        for entity in text.split(';').filter(|e| !e.trim().is_empty()) {
+            let fields = entity.splitn(9, ':').map(str::trim).collect::<Vec<_>>();
+            if fields.len() < 9 {
+                return Err(format!("Broken entity in cty.dat: {}", entity.trim()));
+            }
+            if fields[7].starts_with('*') {
+                continue;
+            }
+            let country = Country {
+                name: fields[0].to_string(),
+                dxcc: None,
+                continent: fields[3].to_string(),
+                cq_zone: parse_zone(fields[1], fields[0])?,
+                itu_zone: parse_zone(fields[2], fields[0])?,
+            };
+            file.add_entity(&country, fields[8].split(','));
+        }
+        Ok(file)
+    }
+
+    /// `Prefix,Name,DXCC,Cont,CQ,ITU,Lat,Lon,TZ,Aliases;`, aliases split by spaces.
+    pub fn parse_csv(text: &str) -> Result<CountryFile, String> {
+        let mut file = CountryFile::new();
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let fields = line.splitn(10, ',').map(str::trim).collect::<Vec<_>>();
+            if fields.len() < 10 {
+                return Err(format!("Broken line in cty.csv: {line}"));
+            }
+            if fields[0].starts_with('*') {
+                continue;
+            }
+            let country = Country {
+                name: fields[1].to_string(),
+                dxcc: Some(
+                    fields[2]
+                        .parse::<u32>()
+                        .map_err(|e| format!("Parse DXCC of {} failed: {e}", fields[1]))?,
+                ),
+                continent: fields[3].to_string(),
+                cq_zone: parse_zone(fields[4], fields[1])?,
+                itu_zone: parse_zone(fields[5], fields[1])?,
+            };
+            file.add_entity(&country, fields[9].trim_end_matches(';').split(' '));
+        }
+        Ok(file)
+    }
+
+    fn new() -> CountryFile {
+        CountryFile {
+            entities: 0,
+            prefixes: HashMap::new(),
+            exact: HashMap::new(),
+        }
+    }
+
+    fn add_entity<'a>(&mut self, country: &Country, aliases: impl Iterator<Item = &'a str>) {
+        self.entities += 1;
+        for alias in aliases.map(str::trim).filter(|a| !a.is_empty()) {
+            let (exact, alias) = match alias.strip_prefix('=') {
+                Some(alias) => (true, alias),
+                None => (false, alias),
+            };
+            let end = alias.find(['(', '[', '<', '{', '~']).unwrap_or(alias.len());
+            let mut country = country.clone();
+            if let Some(zone) = enclosed(alias, '(', ')').and_then(|z| z.parse().ok()) {
+                country.cq_zone = zone;
+            }
+            if let Some(zone) = enclosed(alias, '[', ']').and_then(|z| z.parse().ok()) {
+                country.itu_zone = zone;
+            }
+            if let Some(continent) = enclosed(alias, '{', '}') {
+                country.continent = continent.to_string();
+            }
+            let call = alias[..end].to_uppercase();
+            if exact {
+                self.exact.insert(call, country);
+            } else {
+                self.prefixes.insert(call, country);
+            }
+        }
+    }
+
+    pub fn entities(&self) -> usize {
+        self.entities
+    }
+
+    /// Country of a callsign. Portable prefixes like KH6/W1AW and area suffixes
+    /// like BY1AA/3 are followed, maritime and aeronautical mobile have none.
+    pub fn resolve(&self, callsign: &str) -> Option<Country> {
+        let callsign = callsign.trim().to_uppercase();
+        if let Some(country) = self.exact.get(&callsign) {
+            return Some(country.clone());
+        }
+        let prefix = match Callsign::parse(&callsign) {
+            Ok(callsign) => callsign.dxcc_prefix()?,
+            Err(_) => callsign,
+        };
+        if let Some(country) = self.exact.get(&prefix) {
+            return Some(country.clone());
+        }
+        (1..=prefix.len())
+            .rev()
+            .filter(|len| prefix.is_char_boundary(*len))
+            .find_map(|len| self.prefixes.get(&prefix[..len]))
+            .cloned()
+    }
+}
+
+fn enclosed(text: &str, open: char, close: char) -> Option<&str> {
+    let start = text.find(open)? + 1;
+    let end = start + text[start..].find(close)?;
+    Some(&text[start..end])
+}
+
+fn parse_zone(text: &str, name: &str) -> Result<u32, String> {
+    text.parse::<u32>()
+        .map_err(|e| format!("Parse zone {text} of {name} failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTY_DAT: &str = "\
+China:                    24:  44:  AS:   36.00:  -102.00:    -8.0:  BY:
+    3H,3H0(23)[42],BA,BG,BG9(23)[33],BY,=BA4DW(23);
+United States:            05:  08:  NA:   37.53:    91.67:     5.0:  K:
+    AA,K,W,=W1AW(4);
+Hawaii:                   31:  61:  OC:   21.12:   157.48:    10.0:  KH6:
+    AH6,KH6,NH6,WH6;
+Sicily:                   15:  28:  EU:   37.50:   -14.00:    -1.0:  *IT9:
+    IT9,IG9;
+";
+
+    const CTY_CSV: &str = "\
+BY,China,318,AS,24,44,36.00,-102.00,-8.0,3H 3H0(23)[42] BA BG BG9(23)[33] BY =BA4DW(23);
+K,United States,291,NA,05,08,37.53,91.67,5.0,AA K W =W1AW(4);
+*IT9,Sicily,248,EU,15,28,37.50,-14.00,-1.0,IT9 IG9;
+";
+
+    fn zones(country: Option<Country>) -> Option<(String, u32, u32)> {
+        country.map(|c| (c.name, c.cq_zone, c.itu_zone))
+    }
+
+    #[test]
+    fn parse_dat() {
+        let file = CountryFile::parse_dat(CTY_DAT).unwrap();
+        assert_eq!(file.entities(), 3);
+        assert_eq!(
+            file.resolve("bg7ijk"),
+            Some(Country {
+                name: "China".to_string(),
+                dxcc: None,
+                continent: "AS".to_string(),
+                cq_zone: 24,
+                itu_zone: 44,
+            })
+        );
+        assert!(CountryFile::parse_dat("China: 24: 44: AS;").is_err());
+    }
+
+    #[test]
+    fn parse_csv() {
+        let file = CountryFile::parse_csv(CTY_CSV).unwrap();
+        assert_eq!(file.entities(), 2);
+        let china = file.resolve("BG7IJK").unwrap();
+        assert_eq!((china.dxcc, china.continent.as_str()), (Some(318), "AS"));
+        assert_eq!(file.resolve("W1AW").map(|c| c.cq_zone), Some(4));
+        assert!(CountryFile::parse_csv("BY,China,318,AS").is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let file = CountryFile::parse_dat(CTY_DAT).unwrap();
+        let country = |name: &str, cq_zone, itu_zone| Some((name.to_string(), cq_zone, itu_zone));
+        // Plain prefix, the longest one that matches wins.
+        assert_eq!(zones(file.resolve("W1AB")), country("United States", 5, 8));
+        assert_eq!(zones(file.resolve("BG7IJK")), country("China", 24, 44));
+        // Exact calls and zone overrides.
+        assert_eq!(zones(file.resolve("W1AW")), country("United States", 4, 8));
+        assert_eq!(zones(file.resolve("BA4DW")), country("China", 23, 44));
+        assert_eq!(zones(file.resolve("BA4DX")), country("China", 24, 44));
+        assert_eq!(zones(file.resolve("BG9XD")), country("China", 23, 33));
+        assert_eq!(zones(file.resolve("3H0A")), country("China", 23, 42));
+        // Portable prefixes and area suffixes.
+        assert_eq!(zones(file.resolve("KH6/W1AW")), country("Hawaii", 31, 61));
+        assert_eq!(zones(file.resolve("W1AW/KH6")), country("Hawaii", 31, 61));
+        assert_eq!(zones(file.resolve("BG7IJK/9")), country("China", 23, 33));
+        assert_eq!(
+            zones(file.resolve("W1AB/P")),
+            country("United States", 5, 8)
+        );
+        // Maritime mobile has no entity, WAE only entities are left out.
+        assert_eq!(file.resolve("W1AW/MM"), None);
+        assert_eq!(file.resolve("IT9ABC"), None);
+    }
+}
@@ -0,0 +1,325 @@
+use std::fmt::Display;
+
+/// CTCSS or DCS tone used to open a repeater.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tone {
+    /// Sub-audible tone in Hz, e.g. 88.5
+    Ctcss(f64),
+    /// Digital code, e.g. "023"
+    Dcs(String),
+}
+
+impl Tone {
+    /// Read "T88.5", "C88.5", "88.5Hz" or "D023".
+    pub fn parse(text: &str) -> Result<Tone, String> {
+        let text = text.trim();
+        let upper = text.to_uppercase();
+        if let Some(code) = upper.strip_prefix('D') {
+            let code = code.trim();
+            if !(3..=4).contains(&code.len()) || !code.chars().all(|c| ('0'..='7').contains(&c)) {
+                return Err(format!(
+                    "DCS code {text} should be 3 or 4 octal digits, e.g. D023."
+                ));
+            }
+            return Ok(Tone::Dcs(code.to_string()));
+        }
+        let value = match upper.strip_prefix(['T', 'C']) {
+            Some(value) => value,
+            None => upper
+                .strip_suffix("HZ")
+                .ok_or(format!("Tone {text} should be written like T88.5 or D023."))?,
+        };
+        match value.trim().parse::<f64>() {
+            Ok(hz) if (30.0..=300.0).contains(&hz) => Ok(Tone::Ctcss(hz)),
+            Ok(_) => Err(format!(
+                "CTCSS tone {text} should be between 30 and 300 Hz."
+            )),
+            Err(e) => Err(format!("Cannot read tone {text}: {e}")),
+        }
+    }
+}
+
+impl Display for Tone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tone::Ctcss(hz) => write!(f, "T{hz}"),
+            Tone::Dcs(code) => write!(f, "D{code}"),
+        }
+    }
+}
+
+/// Frequencies of one contact, in MHz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frequency {
+    /// Where I transmit, ADIF FREQ. For satellites this is the uplink.
+    pub(crate) tx: f64,
+    /// Where I listen when it differs from `tx`, ADIF FREQ_RX.
+    /// Repeater output or satellite downlink.
+    pub(crate) rx: Option<f64>,
+    /// Tone needed to open the repeater.
+    pub(crate) tone: Option<Tone>,
+}
+
+impl Frequency {
+    /// Read the frequency as written in the log. The first value is where I
+    /// listen, an optional signed shift or second value is where I transmit,
+    /// and an optional tone follows. Values are MHz unless a unit is given.
+    ///
+    /// "145.500", "7074 kHz", "438.500/-7.6", "438.500/-7.6/T88.5",
+    /// "436.795/145.850" (satellite downlink / uplink).
+    pub fn parse(text: &str) -> Result<Frequency, String> {
+        let parts = text.split('/').map(|p| p.trim()).collect::<Vec<_>>();
+        let listen = parse_mhz(parts[0])?;
+
+        let mut transmit = None;
+        let mut tone = None;
+        for part in &parts[1..] {
+            let upper = part.to_uppercase();
+            let is_tone = upper.starts_with(['T', 'C', 'D'])
+                || (upper.ends_with("HZ")
+                    && !["KHZ", "MHZ", "GHZ"].iter().any(|u| upper.ends_with(u)));
+            if is_tone {
+                if tone.is_some() {
+                    return Err(format!("Frequency {text} has more than one tone."));
+                }
+                tone = Some(Tone::parse(part)?);
+                continue;
+            }
+            if transmit.is_some() {
+                return Err(format!(
+                    "Frequency {text} has more than one shift, write it like 438.500/-7.6/T88.5."
+                ));
+            }
+            transmit = Some(if part.starts_with(['+', '-']) {
+                // Round to 1 Hz so 14.195 + 0.005 is stored as 14.2.
+                ((listen + parse_signed_mhz(part)?) * 1e6).round() / 1e6
+            } else {
+                parse_mhz(part)?
+            });
+        }
+
+        Ok(match transmit {
+            Some(tx) if (tx - listen).abs() > 1e-9 => Frequency {
+                tx,
+                rx: Some(listen),
+                tone,
+            },
+            _ => Frequency {
+                tx: listen,
+                rx: None,
+                tone,
+            },
+        })
+    }
+
+    /// Shift from where I listen to where I transmit, negative for a minus shift repeater.
+    pub fn offset(&self) -> Option<f64> {
+        self.rx.map(|rx| self.tx - rx)
+    }
+
+    pub fn band(&self) -> Result<&'static str, String> {
+        get_band(self.tx)
+    }
+
+    pub fn rx_band(&self) -> Option<Result<&'static str, String>> {
+        self.rx.map(get_band)
+    }
+}
+
+impl Display for Frequency {
+    /// Written back in the same shape `parse` reads: a shift when both
+    /// frequencies are on one band, otherwise both values.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.rx, self.offset()) {
+            (Some(rx), Some(offset)) if get_band(rx).is_ok() && get_band(rx) == self.band() => {
+                let sign = if offset < 0.0 { "-" } else { "+" };
+                write!(f, "{}/{sign}{}", fmt_mhz(rx), fmt_mhz(offset.abs()))?
+            }
+            (Some(rx), _) => write!(f, "{}/{}", fmt_mhz(rx), fmt_mhz(self.tx))?,
+            (None, _) => write!(f, "{}", fmt_mhz(self.tx))?,
+        }
+        if let Some(tone) = &self.tone {
+            write!(f, "/{tone}")?;
+        }
+        Ok(())
+    }
+}
+
+/// MHz with up to 6 decimals and no trailing zeros, "438.5" rather than "438.500000".
+pub fn fmt_mhz(mhz: f64) -> String {
+    let str = format!("{mhz:.6}");
+    str.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Read a positive value with an optional Hz/kHz/MHz/GHz unit into MHz.
+fn parse_mhz(text: &str) -> Result<f64, String> {
+    let mhz = parse_signed_mhz(text)?;
+    if mhz <= 0.0 {
+        return Err(format!("Frequency {text} should be positive."));
+    }
+    Ok(mhz)
+}
+
+fn parse_signed_mhz(text: &str) -> Result<f64, String> {
+    let upper = text.trim().to_uppercase();
+    let (number, scale) = if let Some(n) = upper.strip_suffix("GHZ") {
+        (n, 1000.0)
+    } else if let Some(n) = upper.strip_suffix("MHZ") {
+        (n, 1.0)
+    } else if let Some(n) = upper.strip_suffix("KHZ") {
+        (n, 0.001)
+    } else if let Some(n) = upper.strip_suffix("HZ") {
+        (n, 0.000_001)
+    } else if let Some(n) = upper.strip_suffix('G') {
+        (n, 1000.0)
+    } else if let Some(n) = upper.strip_suffix('M') {
+        (n, 1.0)
+    } else if let Some(n) = upper.strip_suffix('K') {
+        (n, 0.001)
+    } else {
+        (upper.as_str(), 1.0)
+    };
+    match number.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(value * scale),
+        Ok(_) => Err(format!("Frequency {text} is not a number.")),
+        Err(e) => Err(format!("Cannot read frequency {text}: {e}")),
+    }
+}
+
+/// ADIF bands with their lower and upper edges in MHz, as listed in the standard's III.B.4.
+pub const BANDS: [(&str, f64, f64); 33] = [
+    ("2190m", 0.1357, 0.1378),
+    ("630m", 0.472, 0.479),
+    ("560m", 0.501, 0.504),
+    ("160m", 1.8, 2.0),
+    ("80m", 3.5, 4.0),
+    ("60m", 5.06, 5.45),
+    ("40m", 7.0, 7.3),
+    ("30m", 10.1, 10.15),
+    ("20m", 14.0, 14.35),
+    ("17m", 18.068, 18.168),
+    ("15m", 21.0, 21.45),
+    ("12m", 24.890, 24.99),
+    ("10m", 28.0, 29.7),
+    ("8m", 40.0, 45.0),
+    ("6m", 50.0, 54.0),
+    ("5m", 54.000001, 69.9),
+    ("4m", 70.0, 71.0),
+    ("2m", 144.0, 148.0),
+    ("1.25m", 222.0, 225.0),
+    ("70cm", 420.0, 450.0),
+    ("33cm", 902.0, 928.0),
+    ("23cm", 1240.0, 1300.0),
+    ("13cm", 2300.0, 2450.0),
+    ("9cm", 3300.0, 3500.0),
+    ("6cm", 5650.0, 5925.0),
+    ("3cm", 10000.0, 10500.0),
+    ("1.25cm", 24000.0, 24250.0),
+    ("6mm", 47000.0, 47200.0),
+    ("4mm", 75500.0, 81000.0),
+    ("2.5mm", 119980.0, 123000.0),
+    ("2mm", 134000.0, 149000.0),
+    ("1mm", 241000.0, 250000.0),
+    ("submm", 300000.0, 7500000.0),
+];
+
+/// ADIF band name of a frequency in MHz.
+pub fn get_band(freq_mhz: f64) -> Result<&'static str, String> {
+    BANDS
+        .iter()
+        .find(|(_, lower, upper)| (*lower..=*upper).contains(&freq_mhz))
+        .map(|(band, _, _)| *band)
+        .ok_or(format!(
+            "Cannot parse {freq_mhz} because it is not in the standard's band range, as shown in III.B.4."
+        ))
+}
+
+/// Lower and upper edges in MHz of an ADIF band, case insensitive.
+pub fn band_range(band: &str) -> Option<(f64, f64)> {
+    BANDS
+        .iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(band))
+        .map(|(_, lower, upper)| (*lower, *upper))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mhz(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual}");
+    }
+
+    #[test]
+    fn parse_simplex() {
+        let freq = Frequency::parse("145.500").unwrap();
+        assert_mhz(freq.tx, 145.5);
+        assert_eq!(freq.rx, None);
+        assert_eq!(freq.tone, None);
+        assert_mhz(Frequency::parse("7074 kHz").unwrap().tx, 7.074);
+        assert_mhz(Frequency::parse("10G").unwrap().tx, 10000.0);
+    }
+
+    #[test]
+    fn parse_repeater_with_tone() {
+        let freq = Frequency::parse("438.500/-7.6/T88.5").unwrap();
+        assert_mhz(freq.tx, 430.9);
+        assert_mhz(freq.rx.unwrap(), 438.5);
+        assert_eq!(freq.tone, Some(Tone::Ctcss(88.5)));
+        assert_mhz(freq.offset().unwrap(), -7.6);
+        assert_eq!(freq.to_string(), "438.5/-7.6/T88.5");
+    }
+
+    #[test]
+    fn parse_small_shift_is_rounded() {
+        let freq = Frequency::parse("14.195/+0.005").unwrap();
+        assert_eq!(freq.tx, 14.2);
+        assert_eq!(freq.rx, Some(14.195));
+    }
+
+    #[test]
+    fn parse_satellite() {
+        let freq = Frequency::parse("436.795/145.850").unwrap();
+        assert_mhz(freq.tx, 145.85);
+        assert_mhz(freq.rx.unwrap(), 436.795);
+        assert_eq!(freq.to_string(), "436.795/145.85");
+    }
+
+    #[test]
+    fn parse_frequency_errors() {
+        assert!(Frequency::parse("").is_err());
+        assert!(Frequency::parse("junk").is_err());
+        assert!(Frequency::parse("-145").is_err());
+        assert!(Frequency::parse("145.5/T88.5/D023").is_err());
+        assert!(Frequency::parse("438.5/-7.6/+5").is_err());
+    }
+
+    #[test]
+    fn parse_tone() {
+        assert_eq!(Tone::parse("T88.5"), Ok(Tone::Ctcss(88.5)));
+        assert_eq!(Tone::parse("c88.5"), Ok(Tone::Ctcss(88.5)));
+        assert_eq!(Tone::parse("88.5Hz"), Ok(Tone::Ctcss(88.5)));
+        assert_eq!(Tone::parse("D023"), Ok(Tone::Dcs("023".to_string())));
+        assert_eq!(Tone::parse("d0754"), Ok(Tone::Dcs("0754".to_string())));
+        assert_eq!(Tone::Dcs("023".to_string()).to_string(), "D023");
+        assert_eq!(Tone::Ctcss(88.5).to_string(), "T88.5");
+    }
+
+    #[test]
+    fn parse_tone_errors() {
+        assert!(Tone::parse("D23").is_err());
+        assert!(Tone::parse("D02345").is_err());
+        assert!(Tone::parse("D088").is_err());
+        assert!(Tone::parse("T400").is_err());
+        assert!(Tone::parse("88.5").is_err());
+    }
+
+    #[test]
+    fn bands() {
+        assert_eq!(get_band(7.074), Ok("40m"));
+        assert_eq!(get_band(145.5), Ok("2m"));
+        assert!(get_band(100.0).is_err());
+        assert_eq!(band_range("2m"), Some((144.0, 148.0)));
+        assert_eq!(band_range("nope"), None);
+    }
+}
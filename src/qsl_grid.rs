@@ -0,0 +1,367 @@
+/// Mean earth radius in kilometres, used for great circle distance.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Check a 4, 6 or 8 character Maidenhead locator and return it in the usual
+/// spelling, e.g. "om89ab" becomes "OM89ab".
+pub fn normalize_grid(grid: &str) -> Result<String, String> {
+    let grid = grid.trim();
+    let chars = grid.chars().collect::<Vec<_>>();
+    if ![4, 6, 8].contains(&chars.len()) {
+        return Err(format!(
+            "Locator {grid} should have 4, 6 or 8 characters, e.g. OM89 or OM89ab."
+        ));
+    }
+
+    let mut result = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        let valid = match i {
+            0 | 1 => ('A'..='R').contains(&c.to_ascii_uppercase()),
+            4 | 5 => ('A'..='X').contains(&c.to_ascii_uppercase()),
+            _ => c.is_ascii_digit(),
+        };
+        if !valid {
+            return Err(format!(
+                "Locator {grid} is invalid at character {}: field is A-R, square is 0-9, subsquare is A-X.",
+                i + 1
+            ));
+        }
+        result.push(if i < 4 {
+            c.to_ascii_uppercase()
+        } else {
+            c.to_ascii_lowercase()
+        });
+    }
+    Ok(result)
+}
+
+/// Latitude and longitude in degrees of the centre of a locator.
+pub fn grid_to_lat_lon(grid: &str) -> Result<(f64, f64), String> {
+    let grid = normalize_grid(grid)?;
+    let bytes = grid.to_ascii_uppercase().into_bytes();
+
+    let mut lon = -180.0;
+    let mut lat = -90.0;
+    // Size of one field, square, subsquare and extended square in degrees.
+    let mut lon_size = 20.0;
+    let mut lat_size = 10.0;
+    for (pair, chunk) in bytes.chunks(2).enumerate() {
+        let base = if pair % 2 == 0 { b'A' } else { b'0' };
+        if pair > 0 {
+            let divisions = if pair % 2 == 0 { 24.0 } else { 10.0 };
+            lon_size /= divisions;
+            lat_size /= divisions;
+        }
+        lon += (chunk[0] - base) as f64 * lon_size;
+        lat += (chunk[1] - base) as f64 * lat_size;
+    }
+
+    Ok((lat + lat_size / 2.0, lon + lon_size / 2.0))
+}
+
+/// Great circle distance in kilometres and initial bearing in degrees from `from` to `to`.
+pub fn distance_and_bearing(from: (f64, f64), to: (f64, f64)) -> (f64, f64) {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = lon2 - lon1;
+
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    let distance = 2.0 * EARTH_RADIUS_KM * a.sqrt().atan2((1.0 - a).sqrt());
+
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+    let bearing = (y.atan2(x).to_degrees() + 360.0) % 360.0;
+
+    (distance, bearing)
+}
+
+/// Six character locator of a position, e.g. (39.9, 116.4) becomes "OM89ev".
+pub fn lat_lon_to_grid(lat: f64, lon: f64) -> String {
+    // Keep the north pole and the antimeridian inside the last square.
+    let lat = (lat + 90.0).clamp(0.0, 179.999_999);
+    let lon = (lon + 180.0).clamp(0.0, 359.999_999);
+
+    let mut grid = String::new();
+    grid.push((b'A' + (lon / 20.0) as u8) as char);
+    grid.push((b'A' + (lat / 10.0) as u8) as char);
+    grid.push((b'0' + (lon % 20.0 / 2.0) as u8) as char);
+    grid.push((b'0' + (lat % 10.0) as u8) as char);
+    grid.push((b'a' + (lon % 2.0 * 12.0) as u8) as char);
+    grid.push((b'a' + (lat % 1.0 * 24.0) as u8) as char);
+    grid
+}
+
+enum CoordinateToken {
+    Number(f64),
+    Hemisphere(char),
+}
+
+/// Parse a position typed by hand or copied from a GPS app into (latitude, longitude).
+/// Accepts decimal degrees ("39.9042, 116.4074", "-33.86 151.21"),
+/// degrees and minutes and DMS ("39°54'15\"N 116°24'27\"E", "N39 54.25 E116 24.45").
+pub fn parse_coordinates(text: &str) -> Result<(f64, f64), String> {
+    let mut tokens = Vec::new();
+    let mut number = String::new();
+    let chars = text.chars().chain(std::iter::once(' ')).collect::<Vec<_>>();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_digit() || c == '.' || ((c == '-' || c == '+') && number.is_empty()) {
+            number.push(c);
+            continue;
+        }
+        if !number.is_empty() {
+            match number.parse::<f64>() {
+                Ok(n) => tokens.push(CoordinateToken::Number(n)),
+                Err(e) => return Err(format!("Cannot read number {number}: {e}")),
+            }
+            number.clear();
+        }
+        if !c.is_alphabetic() {
+            continue;
+        }
+        // A hemisphere letter is written right before or after a number, like
+        // N39 or 39°54'15"N, so words such as West are not taken for one.
+        let after_number =
+            i > 0 && (chars[i - 1].is_ascii_digit() || "°'\"′″".contains(chars[i - 1]));
+        let before_number = chars[i + 1].is_ascii_digit();
+        match c.to_ascii_uppercase() {
+            h @ ('N' | 'S' | 'E' | 'W') if after_number || before_number => {
+                tokens.push(CoordinateToken::Hemisphere(h));
+            }
+            _ => {
+                return Err(format!(
+                    "Position {text} has {c} where a number or N/S/E/W next to one is expected."
+                ));
+            }
+        }
+    }
+
+    // Numbers with their hemisphere letter, if any.
+    let mut groups: Vec<(Vec<f64>, Option<char>)> = Vec::new();
+    let has_hemisphere = tokens
+        .iter()
+        .any(|t| matches!(t, CoordinateToken::Hemisphere(_)));
+    if !has_hemisphere {
+        let numbers = tokens
+            .iter()
+            .filter_map(|t| match t {
+                CoordinateToken::Number(n) => Some(*n),
+                CoordinateToken::Hemisphere(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if numbers.is_empty() || numbers.len() % 2 != 0 || numbers.len() > 6 {
+            return Err(format!(
+                "Cannot read position {text}, write it like 39.9042, 116.4074."
+            ));
+        }
+        let (lat, lon) = numbers.split_at(numbers.len() / 2);
+        groups.push((lat.to_vec(), None));
+        groups.push((lon.to_vec(), None));
+    } else if matches!(tokens.first(), Some(CoordinateToken::Hemisphere(_))) {
+        // Prefix style, N39 54.25 E116 24.45
+        for token in tokens {
+            match token {
+                CoordinateToken::Hemisphere(h) => groups.push((Vec::new(), Some(h))),
+                CoordinateToken::Number(n) => groups.last_mut().unwrap().0.push(n),
+            }
+        }
+    } else {
+        // Suffix style, 39°54'15"N 116°24'27"E
+        let mut numbers = Vec::new();
+        for token in tokens {
+            match token {
+                CoordinateToken::Number(n) => numbers.push(n),
+                CoordinateToken::Hemisphere(h) => {
+                    groups.push((std::mem::take(&mut numbers), Some(h)))
+                }
+            }
+        }
+        if !numbers.is_empty() {
+            return Err(format!(
+                "Position {text} has numbers after the last N/S/E/W."
+            ));
+        }
+    }
+
+    if groups.len() != 2 {
+        return Err(format!(
+            "Position {text} should have exactly one latitude and one longitude."
+        ));
+    }
+    let lat = coordinate_from_parts(&groups[0].0, groups[0].1, true)?;
+    let lon = coordinate_from_parts(&groups[1].0, groups[1].1, false)?;
+    Ok((lat, lon))
+}
+
+/// Combine degrees, optional minutes and seconds and the hemisphere into signed degrees.
+fn coordinate_from_parts(
+    parts: &[f64],
+    hemisphere: Option<char>,
+    is_lat: bool,
+) -> Result<f64, String> {
+    let name = if is_lat { "Latitude" } else { "Longitude" };
+    let (positive, negative, limit) = if is_lat {
+        ('N', 'S', 90.0)
+    } else {
+        ('E', 'W', 180.0)
+    };
+
+    if parts.is_empty() || parts.len() > 3 {
+        return Err(format!(
+            "{name} should be degrees, minutes and seconds at most."
+        ));
+    }
+    if parts[1..].iter().any(|p| !(0.0..60.0).contains(p)) {
+        return Err(format!(
+            "{name} minutes and seconds should be between 0 and 60."
+        ));
+    }
+    let mut value = parts[0].abs();
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        value += part / 60f64.powi(i as i32);
+    }
+    if parts[0].is_sign_negative() {
+        value = -value;
+    }
+
+    match hemisphere {
+        None => {}
+        Some(h) if h == positive => {}
+        Some(h) if h == negative => value = -value,
+        Some(h) => return Err(format!("{name} cannot use hemisphere {h}.")),
+    }
+    if value.abs() > limit {
+        return Err(format!("{name} {value} is out of range."));
+    }
+    Ok(value)
+}
+
+/// ADIF Location value of a coordinate, "XDDD MM.MMM", e.g. "N039 54.252".
+pub fn format_adif_location(value: f64, is_lat: bool) -> String {
+    let hemisphere = match (is_lat, value < 0.0) {
+        (true, false) => 'N',
+        (true, true) => 'S',
+        (false, false) => 'E',
+        (false, true) => 'W',
+    };
+    // Round to the minute precision first so 59.9999 does not print as 60.000.
+    let thousandths = (value.abs() * 60_000.0).round() as u64;
+    let degrees = thousandths / 60_000;
+    let minutes = (thousandths % 60_000) as f64 / 1000.0;
+    format!("{hemisphere}{degrees:03} {minutes:06.3}")
+}
+
+/// Read an ADIF Location value back into signed degrees.
+pub fn parse_adif_location(text: &str, is_lat: bool) -> Result<f64, String> {
+    let text = text.trim();
+    let first = text.chars().next().ok_or("Location is empty")?;
+    let hemisphere = first.to_ascii_uppercase();
+    let (degrees, minutes) = text[first.len_utf8()..]
+        .trim()
+        .split_once(' ')
+        .ok_or(format!("Location {text} is not in XDDD MM.MMM format"))?;
+    let degrees = degrees
+        .parse::<f64>()
+        .map_err(|e| format!("Location {text} has invalid degrees: {e}"))?;
+    let minutes = minutes
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("Location {text} has invalid minutes: {e}"))?;
+    coordinate_from_parts(&[degrees, minutes], Some(hemisphere), is_lat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_degrees(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-6 && (actual.1 - expected.1).abs() < 1e-6,
+            "{actual:?} is not {expected:?}"
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(normalize_grid(" om89ab "), Ok("OM89ab".to_string()));
+        assert_eq!(normalize_grid("OM89"), Ok("OM89".to_string()));
+        assert_eq!(normalize_grid("om89ab12"), Ok("OM89ab12".to_string()));
+        assert!(normalize_grid("OM8").is_err());
+        assert!(normalize_grid("SM89").is_err());
+        assert!(normalize_grid("OM89az").is_err());
+    }
+
+    #[test]
+    fn grid_centre() {
+        assert_degrees(grid_to_lat_lon("OM89").unwrap(), (39.5, 117.0));
+        assert_degrees(grid_to_lat_lon("JJ00").unwrap(), (0.5, 1.0));
+        assert_degrees(
+            grid_to_lat_lon("OM89ev").unwrap(),
+            (39.0 + 21.5 / 24.0, 116.0 + 4.5 / 12.0),
+        );
+    }
+
+    #[test]
+    fn distance() {
+        let (distance, bearing) = distance_and_bearing((0.0, 0.0), (0.0, 90.0));
+        assert!((distance - 10007.5).abs() < 0.1, "{distance}");
+        assert!((bearing - 90.0).abs() < 1e-9, "{bearing}");
+        let (_, bearing) = distance_and_bearing((0.0, 0.0), (10.0, 0.0));
+        assert!(bearing.abs() < 1e-9, "{bearing}");
+    }
+
+    #[test]
+    fn position_to_grid() {
+        assert_eq!(lat_lon_to_grid(39.9, 116.4), "OM89ev");
+        assert_eq!(lat_lon_to_grid(-33.86, 151.21), "QF56od");
+        assert_eq!(lat_lon_to_grid(90.0, 180.0), "RR99xx");
+        let (lat, lon) = grid_to_lat_lon("FN31pr").unwrap();
+        assert_eq!(lat_lon_to_grid(lat, lon), "FN31pr");
+    }
+
+    #[test]
+    fn coordinates() {
+        assert_degrees(
+            parse_coordinates("39.9042, 116.4074").unwrap(),
+            (39.9042, 116.4074),
+        );
+        assert_degrees(
+            parse_coordinates("-33.86 151.21").unwrap(),
+            (-33.86, 151.21),
+        );
+        assert_degrees(
+            parse_coordinates("39°54'15\"N 116°24'27\"W").unwrap(),
+            (
+                39.0 + 54.0 / 60.0 + 15.0 / 3600.0,
+                -(116.0 + 24.0 / 60.0 + 27.0 / 3600.0),
+            ),
+        );
+        assert_degrees(
+            parse_coordinates("S39 30 E116 15").unwrap(),
+            (-39.5, 116.25),
+        );
+        assert_degrees(parse_coordinates("39.9n 116.4e").unwrap(), (39.9, 116.4));
+        assert!(parse_coordinates("39.9").is_err());
+        assert!(parse_coordinates("91 0").is_err());
+        assert!(parse_coordinates("39 60 116 0 0 0").is_err());
+    }
+
+    #[test]
+    fn coordinates_in_words() {
+        assert!(parse_coordinates("West 30").is_err());
+        assert!(parse_coordinates("North 39.9 East 116.4").is_err());
+        assert!(parse_coordinates("39.9 N 116.4 E").is_err());
+        assert!(parse_coordinates("lat 39.9 lon 116.4").is_err());
+    }
+
+    #[test]
+    fn adif_location() {
+        assert_eq!(format_adif_location(39.9042, true), "N039 54.252");
+        assert_eq!(format_adif_location(-116.4074, false), "W116 24.444");
+        assert_eq!(format_adif_location(59.99999999, true), "N060 00.000");
+        let lat = parse_adif_location("N039 54.252", true).unwrap();
+        assert!((lat - 39.9042).abs() < 1e-9, "{lat}");
+        assert_eq!(parse_adif_location("s033 30.000", true), Ok(-33.5));
+        assert!(parse_adif_location("E181 00.000", false).is_err());
+        assert!(parse_adif_location("N039", true).is_err());
+    }
+}
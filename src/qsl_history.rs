@@ -0,0 +1,127 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use std::fmt::Display;
+
+/// What happened to a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryAction {
+    Insert,
+    Update,
+    Delete,
+    /// Put back to the version before an earlier change
+    Revert,
+    /// Taken out of the trash
+    Restore,
+    /// Removed from the trash for good
+    Purge,
+}
+
+impl HistoryAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryAction::Insert => "INSERT",
+            HistoryAction::Update => "UPDATE",
+            HistoryAction::Delete => "DELETE",
+            HistoryAction::Revert => "REVERT",
+            HistoryAction::Restore => "RESTORE",
+            HistoryAction::Purge => "PURGE",
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<HistoryAction, String> {
+        match text {
+            "INSERT" => Ok(HistoryAction::Insert),
+            "UPDATE" => Ok(HistoryAction::Update),
+            "DELETE" => Ok(HistoryAction::Delete),
+            "REVERT" => Ok(HistoryAction::Revert),
+            "RESTORE" => Ok(HistoryAction::Restore),
+            "PURGE" => Ok(HistoryAction::Purge),
+            _ => Err(format!("Unknown history action {text}")),
+        }
+    }
+}
+
+impl Display for HistoryAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// One change of a record. The record is kept whole before and after the
+/// change, as a JSON object of its columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub(crate) id: i32,
+    pub(crate) qsl_id: i32,
+    pub(crate) action: HistoryAction,
+    pub(crate) changed_at: DateTime<Utc>,
+    /// Callsign of the station profile that was active
+    pub(crate) profile: Option<String>,
+    /// None for an insert
+    pub(crate) old_record: Option<String>,
+    /// None for a purge
+    pub(crate) new_record: Option<String>,
+}
+
+/// A column a change touched, none where the record did not exist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub(crate) field: String,
+    pub(crate) old_value: Option<String>,
+    pub(crate) new_value: Option<String>,
+}
+
+impl HistoryEntry {
+    /// One line for the history list, e.g. "2025-07-01 18:00 UPDATE #12 by BY9CLB".
+    pub fn summary(&self, time_zone: &FixedOffset) -> String {
+        let mut line = format!(
+            "{} {} #{}",
+            self.changed_at
+                .with_timezone(time_zone)
+                .format("%Y-%m-%d %H:%M:%S"),
+            self.action,
+            self.qsl_id
+        );
+        if let Some(profile) = &self.profile {
+            line.push_str(&format!(" by {profile}"));
+        }
+        line
+    }
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            self.old_value.as_deref().unwrap_or("-"),
+            self.new_value.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+/// An edit made in the table this session, kept to undo and redo it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Add(i32),
+    /// Snapshots and tags of the record before and after
+    Update {
+        id: i32,
+        before: String,
+        after: String,
+        tags_before: Vec<String>,
+        tags_after: Vec<String>,
+    },
+    /// Moved to the trash
+    Delete(i32),
+}
+
+impl Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Edit::Add(id) => write!(f, "adding record {id}"),
+            Edit::Update { id, .. } => write!(f, "updating record {id}"),
+            Edit::Delete(id) => write!(f, "deleting record {id}"),
+        }
+    }
+}
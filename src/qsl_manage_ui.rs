@@ -1,21 +1,34 @@
+use crate::qsl_adif_helper::ADIF_MODES;
+use crate::qsl_callsign::Callsign;
+use crate::qsl_card::{QSLCard, QSLRoute, QSLService, QSLStatus};
+use crate::qsl_frequency::{BANDS, Frequency};
+use crate::qsl_grid::{normalize_grid, parse_coordinates};
+use crate::qsl_history::{HistoryAction, HistoryEntry};
 use crate::qsl_manager::QSLManager;
+use crate::qsl_province::{Province, edited_state};
+use crate::qsl_station::{EquipmentPreset, StationProfile};
+use crate::qsl_type::Contact;
 use crate::qsl_type::Mode;
 use crate::qsl_type::QSL;
-use chrono::{Datelike, Timelike};
+use crate::qsl_type::{QSLFilter, QSLSearch, RecordKind, fmt_duration, parse_time_zone};
+use chrono::{
+    DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, TimeDelta, TimeZone, Timelike, Utc,
+};
 use cursive::reexports::log;
 use cursive::view::{Nameable, Resizable, Scrollable};
-use cursive::views::{Dialog, ListView, OnEventView, SelectView};
+use cursive::views::{Checkbox, Dialog, ListView, OnEventView, SelectView};
 use cursive::views::{EditView, LinearLayout, TextView};
-use cursive::{Cursive, event};
+use cursive::{Cursive, With, event};
 use cursive_table_view::{TableView, TableViewItem};
 use std::cmp::Ordering;
-use std::ops::Deref;
+use std::path::Path;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 enum UIColumn {
     Callsign,
     Mode,
     Datetime,
+    Card,
     Note,
 }
 
@@ -25,18 +38,31 @@ impl UIColumn {
             UIColumn::Callsign => "Callsign",
             UIColumn::Mode => "Mode",
             UIColumn::Datetime => "Datetime",
+            UIColumn::Card => "Card",
             UIColumn::Note => "Note",
         }
     }
 }
 
-impl TableViewItem<UIColumn> for QSL {
+/// A record in the table, with the zone its datetime is shown in.
+#[derive(Clone)]
+struct QSLRow {
+    qsl: QSL,
+    time_zone: FixedOffset,
+}
+
+impl TableViewItem<UIColumn> for QSLRow {
     fn to_column(&self, column: UIColumn) -> String {
+        let qsl = &self.qsl;
         match column {
-            UIColumn::Note => self.note.clone().unwrap_or("".parse().unwrap()),
-            UIColumn::Callsign => self.call_number.clone(),
-            UIColumn::Mode => format!("{:?}", self.mode),
-            UIColumn::Datetime => self.datetime.to_string(),
+            UIColumn::Note => qsl.note.clone().unwrap_or("".parse().unwrap()),
+            UIColumn::Callsign => qsl.call_number.clone(),
+            UIColumn::Mode => qsl.mode.to_string(),
+            UIColumn::Datetime => qsl
+                .local_datetime(&self.time_zone)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            UIColumn::Card => qsl.card_state().to_string(),
         }
     }
 
@@ -44,15 +70,17 @@ impl TableViewItem<UIColumn> for QSL {
     where
         Self: Sized,
     {
+        let (qsl, other) = (&self.qsl, &other.qsl);
         match column {
-            UIColumn::Note => self
+            UIColumn::Note => qsl
                 .note
                 .clone()
                 .unwrap_or("".parse().unwrap())
                 .cmp(&other.note.clone().unwrap_or("".parse().unwrap())),
-            UIColumn::Callsign => self.call_number.cmp(&other.call_number),
-            UIColumn::Mode => format!("{:?}", self.mode).cmp(&format!("{:?}", other.mode)),
-            UIColumn::Datetime => self.datetime.cmp(&other.datetime),
+            UIColumn::Callsign => qsl.call_number.cmp(&other.call_number),
+            UIColumn::Mode => qsl.mode.to_string().cmp(&other.mode.to_string()),
+            UIColumn::Datetime => qsl.datetime.cmp(&other.datetime),
+            UIColumn::Card => qsl.card_state().cmp(other.card_state()),
         }
     }
 }
@@ -60,58 +88,110 @@ impl TableViewItem<UIColumn> for QSL {
 pub fn show_qsl_table(s: &mut Cursive) {
     s.pop_layer();
 
-    let mut table = TableView::<QSL, UIColumn>::new()
-        .column(UIColumn::Datetime, UIColumn::Datetime.as_str(), |c| {
-            c.width_percent(35)
-        })
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let time_zone = *qslmanager.time_zone();
+
+    let mut table = TableView::<QSLRow, UIColumn>::new()
+        .column(
+            UIColumn::Datetime,
+            format!("{} ({})", UIColumn::Datetime.as_str(), time_zone),
+            |c| c.width_percent(30),
+        )
         .column(UIColumn::Callsign, UIColumn::Callsign.as_str(), |c| {
-            c.width_percent(20)
+            c.width_percent(18)
         })
         .column(UIColumn::Mode, UIColumn::Mode.as_str(), |c| {
             c.width_percent(10)
         })
+        .column(UIColumn::Card, UIColumn::Card.as_str(), |c| {
+            c.width_percent(12)
+        })
         .column(UIColumn::Note, UIColumn::Note.as_str(), |c| {
-            c.ordering(Ordering::Greater).width_percent(35)
+            c.ordering(Ordering::Greater).width_percent(30)
         });
 
-    let qslmanager = s.user_data::<QSLManager>().unwrap();
-
     let record = qslmanager.fetch_shown_qsl();
     log::debug!(
         "qsl_ui::show_qsl_table: inserting {} item(s).",
         record.len()
     );
-    table.set_items(record);
+    table.set_items(
+        record
+            .into_iter()
+            .map(|qsl| QSLRow { qsl, time_zone })
+            .collect(),
+    );
 
-    let callsign = qslmanager.callsign();
+    let station = qslmanager.station().to_string();
     let page = qslmanager.page;
     let max_page = qslmanager.max_page();
     let number_of_record = qslmanager.number_of_record();
+    let filter = qslmanager.filter().clone();
+    let tab = qslmanager.tab();
+    let tabs = RecordKind::ALL.map(|kind| {
+        let count = qslmanager.tab_count(kind);
+        let pages = count.map(|count| (count, qslmanager.page_count(count)));
+        (kind, pages)
+    });
 
     table.set_on_submit(|siv: &mut Cursive, _row: usize, index: usize| {
         let qsl = siv
-            .call_on_name("table", move |table: &mut TableView<QSL, UIColumn>| {
-                table.borrow_item(index).unwrap().clone()
+            .call_on_name("table", move |table: &mut TableView<QSLRow, UIColumn>| {
+                table.borrow_item(index).unwrap().qsl.clone()
             })
             .unwrap();
 
         edit_record_dialog(siv, Some(qsl));
     });
 
-    let dialog = Dialog::around(
+    let mut dialog = Dialog::around(
         OnEventView::new(table.with_name("table").min_size((70, 20)))
             .on_event(event::Key::Left, |s| previous_page(s))
-            .on_event(event::Key::Right, |s| next_page(s)),
+            .on_event(event::Key::Right, |s| next_page(s))
+            .on_event(event::Event::CtrlChar('z'), undo)
+            .on_event(event::Event::CtrlChar('y'), redo)
+            .on_event('/', search_dialog)
+            .on_event('t', move |s| switch_tab(s, tab.next())),
     )
     .title(format!(
-        "{} page {} / {} total {}",
-        callsign,
+        "{} {} page {} / {} total {}{}",
+        station,
+        tab,
         page + 1,
         max_page + 1,
-        number_of_record
+        number_of_record,
+        match filter {
+            QSLFilter::All => "".to_string(),
+            filter => format!(" ({filter})"),
+        }
     ));
+    // Tabs as buttons with their records and pages, the shown one in brackets and disabled.
+    for (kind, count) in tabs {
+        let label = match count {
+            Ok((count, 1)) => format!("{kind} {count}, 1 page"),
+            Ok((count, pages)) => format!("{kind} {count}, {pages} pages"),
+            Err(e) => {
+                log::error!("qsl_ui::show_qsl_table: {e}");
+                kind.to_string()
+            }
+        };
+        match kind == tab {
+            true => {
+                dialog.add_button(format!("[{label}]"), move |s| switch_tab(s, kind));
+                if let Some(button) = dialog.buttons_mut().last() {
+                    button.disable();
+                }
+            }
+            false => dialog.add_button(label, move |s| switch_tab(s, kind)),
+        };
+    }
     s.add_layer(dialog);
 
+    fn switch_tab(s: &mut Cursive, tab: RecordKind) {
+        s.user_data::<QSLManager>().unwrap().set_tab(tab);
+        show_qsl_table(s);
+    }
+
     fn next_page(s: &mut Cursive) {
         if let Some(qslmanager) = s.user_data::<QSLManager>() {
             if qslmanager.page < qslmanager.max_page() {
@@ -131,10 +211,97 @@ pub fn show_qsl_table(s: &mut Cursive) {
     }
 }
 
+/// Undo the latest add, update or delete made in this session.
+fn undo(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    match qslmanager.undo() {
+        Ok(Some(edit)) => {
+            log::debug!("Undid {edit}.");
+            qslmanager.fetch_shown_qsl();
+            show_qsl_table(s);
+            show_error_dialog(s, &format!("Undid {edit}."));
+        }
+        Ok(None) => show_error_dialog(s, "Nothing to undo."),
+        Err(e) => show_error_dialog(s, &format!("Failed to undo: {e}")),
+    }
+}
+
+fn redo(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    match qslmanager.redo() {
+        Ok(Some(edit)) => {
+            log::debug!("Redid {edit}.");
+            qslmanager.fetch_shown_qsl();
+            show_qsl_table(s);
+            show_error_dialog(s, &format!("Redid {edit}."));
+        }
+        Ok(None) => show_error_dialog(s, "Nothing to redo."),
+        Err(e) => show_error_dialog(s, &format!("Failed to redo: {e}")),
+    }
+}
+
+pub fn filter_records(s: &mut Cursive, filter: QSLFilter) {
+    s.user_data::<QSLManager>().unwrap().set_filter(filter);
+    show_qsl_table(s);
+}
+
 pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
     let is_new = qsl.is_none();
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let time_zone = *qslmanager.time_zone();
+    let active_station = qslmanager.station().clone();
+    let stations = match qslmanager.context.get_station_profiles() {
+        Ok(stations) => stations,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read station profiles: {e}"));
+            return;
+        }
+    };
+    let presets = match qslmanager.context.get_equipment_presets() {
+        Ok(presets) => presets,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read equipment presets: {e}"));
+            return;
+        }
+    };
+    let last_equipment = match qslmanager.last_equipment() {
+        Ok(last) => last,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read the last record: {e}"));
+            return;
+        }
+    };
+    let prefill_from_last = is_new
+        && last_equipment.is_some()
+        && qslmanager
+            .context
+            .get_prefill_from_last()
+            .unwrap_or_default();
 
     let id = if is_new { 0 } else { qsl.as_ref().unwrap().id };
+    let tags = match qslmanager.context.get_tags(id) {
+        Ok(tags) => tags.join(", "),
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read tags: {e}"));
+            return;
+        }
+    };
+    // Kept as they are when no country file is loaded.
+    let country = qsl.as_ref().map(|qsl| {
+        (
+            qsl.dxcc,
+            qsl.country.clone(),
+            qsl.cont.clone(),
+            qsl.cqz,
+            qsl.ituz,
+        )
+    });
+    let (dxcc, country, cont, cqz, ituz) = country.unwrap_or_default();
+    // A state that was derived from the old callsign follows the new one.
+    let derived_state = qsl
+        .as_ref()
+        .and_then(|qsl| Province::from_callsign(&qsl.call_number))
+        .map(|province| province.code);
     let mut call_number = "".to_string();
     let mode = if is_new {
         Mode::EYEBALL
@@ -142,50 +309,213 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
         qsl.as_ref().unwrap().mode.clone()
     };
     let mut freq = "".to_string();
+    let mut sat_name = "".to_string();
+    let mut sat_mode = "".to_string();
 
     let mut year = "".to_string();
     let mut month = "".to_string();
     let mut day = "".to_string();
     let mut hour = "".to_string();
     let mut minute = "".to_string();
+    let mut year_off = "".to_string();
+    let mut month_off = "".to_string();
+    let mut day_off = "".to_string();
+    let mut hour_off = "".to_string();
+    let mut minute_off = "".to_string();
 
+    // New records start with the defaults of the active station.
     let mut rst_me = "".to_string();
-    let mut qth_me = "".to_string();
-    let mut rig_me = "".to_string();
-    let mut watt_me = "".to_string();
-    let mut ant_me = "".to_string();
+    let mut qth_me = active_station.qth.clone().unwrap_or_default();
+    let mut grid_me = active_station.grid.clone().unwrap_or_default();
+    let mut position_me = "".to_string();
+    let mut rig_me = active_station.rig.clone().unwrap_or_default();
+    let mut watt_me = active_station
+        .watt
+        .map_or("".to_string(), |w| w.to_string());
+    let mut ant_me = active_station.ant.clone().unwrap_or_default();
     let mut rst_counterpart = "".to_string();
     let mut qth_counterpart = "".to_string();
+    let mut state = "".to_string();
+    let mut grid_counterpart = "".to_string();
+    let mut position_counterpart = "".to_string();
     let mut rig_counterpart = "".to_string();
     let mut watt_counterpart = "".to_string();
     let mut ant_counterpart = "".to_string();
+    let mut name_counterpart = "".to_string();
+    let mut email_counterpart = "".to_string();
+    let mut address_counterpart = "".to_string();
     let mut note = "".to_string();
+    let mut cards = [QSLCard::default(), QSLCard::default(), QSLCard::default()];
+    let mut qsl_sent_via = None;
+    let mut qsl_rcvd_via = None;
+    let mut qsl_via = "".to_string();
+    let mut station = active_station.id;
+    let mut contest_id = "".to_string();
+    let mut stx = "".to_string();
+    let mut srx = "".to_string();
+    let mut stx_string = "".to_string();
+    let mut srx_string = "".to_string();
+
+    if prefill_from_last && let Some(last) = &last_equipment {
+        qth_me = last.qth.clone().unwrap_or(qth_me);
+        rig_me = last.rig.clone().unwrap_or(rig_me);
+        watt_me = last.watt.map_or(watt_me, |w| w.to_string());
+        ant_me = last.ant.clone().unwrap_or(ant_me);
+    }
 
     if !is_new {
         let qsl = qsl.unwrap();
         call_number = qsl.call_number.clone();
-        freq = qsl.freq.unwrap_or_default();
+        position_me = qsl
+            .my_location()
+            .map_or("".to_string(), |(lat, lon)| format!("{lat:.6}, {lon:.6}"));
+        position_counterpart = qsl
+            .counterpart_location()
+            .map_or("".to_string(), |(lat, lon)| format!("{lat:.6}, {lon:.6}"));
+        freq = qsl.freq.map_or("".to_string(), |f| f.to_string());
+        sat_name = qsl.sat_name.unwrap_or_default();
+        sat_mode = qsl.sat_mode.unwrap_or_default();
 
-        let datetime = qsl.datetime;
+        let datetime = qsl.datetime.with_timezone(&time_zone);
         year = datetime.year().to_string().clone();
         month = datetime.month().to_string();
         day = datetime.day().to_string();
         hour = datetime.hour().to_string();
         minute = datetime.minute().to_string();
+        if let Some(datetime_off) = qsl.datetime_off.map(|d| d.with_timezone(&time_zone)) {
+            year_off = datetime_off.year().to_string();
+            month_off = datetime_off.month().to_string();
+            day_off = datetime_off.day().to_string();
+            hour_off = datetime_off.hour().to_string();
+            minute_off = datetime_off.minute().to_string();
+        }
 
         rst_me = qsl.rst_me.unwrap_or_default();
         qth_me = qsl.qth_me.unwrap_or_default();
+        grid_me = qsl.grid_me.unwrap_or_default();
         rig_me = qsl.rig_me.unwrap_or_default();
         watt_me = qsl.watt_me.unwrap_or_default().to_string();
         ant_me = qsl.ant_me.unwrap_or_default();
 
         rst_counterpart = qsl.rst_counterpart.unwrap_or_default();
         qth_counterpart = qsl.qth_counterpart.unwrap_or_default();
+        state = qsl.state.unwrap_or_default();
+        grid_counterpart = qsl.grid_counterpart.unwrap_or_default();
         rig_counterpart = qsl.rig_counterpart.unwrap_or_default();
         watt_counterpart = qsl.watt_counterpart.unwrap_or_default().to_string();
         ant_counterpart = qsl.ant_counterpart.unwrap_or_default();
+        name_counterpart = qsl.name_counterpart.unwrap_or_default();
+        email_counterpart = qsl.email_counterpart.unwrap_or_default();
+        address_counterpart = qsl.address_counterpart.unwrap_or_default();
         note = qsl.note.unwrap_or_default();
+        cards = [qsl.card_paper, qsl.card_eqsl, qsl.card_lotw];
+        qsl_sent_via = qsl.qsl_sent_via;
+        qsl_rcvd_via = qsl.qsl_rcvd_via;
+        qsl_via = qsl.qsl_via.unwrap_or_default();
+        station = qsl.station_profile.unwrap_or(station);
+        contest_id = qsl.contest_id.unwrap_or_default();
+        stx = qsl.stx.map_or("".to_string(), |n| n.to_string());
+        srx = qsl.srx.map_or("".to_string(), |n| n.to_string());
+        stx_string = qsl.stx_string.unwrap_or_default();
+        srx_string = qsl.srx_string.unwrap_or_default();
+    }
+
+    let station_items = stations
+        .iter()
+        .map(|station| (station.to_string(), station.id))
+        .collect::<Vec<_>>();
+    // Choosing another station for a new record brings its defaults along.
+    let mut station_select = SelectView::<i32>::new()
+        .popup()
+        .on_submit(move |s, id: &i32| {
+            if !is_new {
+                return;
+            }
+            let Some(station) = stations.iter().find(|station| station.id == *id) else {
+                return;
+            };
+            for (name, value) in [
+                ("qth_me", station.qth.clone().unwrap_or_default()),
+                ("grid_me", station.grid.clone().unwrap_or_default()),
+                ("rig_me", station.rig.clone().unwrap_or_default()),
+                (
+                    "watt_me",
+                    station.watt.map_or("".to_string(), |w| w.to_string()),
+                ),
+                ("ant_me", station.ant.clone().unwrap_or_default()),
+            ] {
+                s.call_on_name(name, |view: &mut EditView| view.set_content(value));
+            }
+        });
+    station_select.add_all(station_items);
+    let index = station_select
+        .iter()
+        .position(|(_, item)| *item == station)
+        .unwrap_or_default();
+    let station_select = station_select.selected(index);
+
+    // A preset only fills the equipment it has, the rest stays as typed.
+    let mut preset_select = SelectView::<Option<EquipmentPreset>>::new()
+        .popup()
+        .on_submit(|s, preset: &Option<EquipmentPreset>| {
+            let Some(preset) = preset else {
+                return;
+            };
+            for (name, value) in [
+                ("qth_me", preset.qth.clone()),
+                ("rig_me", preset.rig.clone()),
+                ("watt_me", preset.watt.map(|w| w.to_string())),
+                ("ant_me", preset.ant.clone()),
+            ] {
+                if let Some(value) = value {
+                    s.call_on_name(name, |view: &mut EditView| view.set_content(value));
+                }
+            }
+        });
+    preset_select.add_item("(none)", None);
+    if let Some(last) = last_equipment {
+        preset_select.add_item(last.name.clone(), Some(last));
+    }
+    for preset in presets {
+        preset_select.add_item(preset.name.clone(), Some(preset));
+    }
+    let preset_select = preset_select.selected(if prefill_from_last { 1 } else { 0 });
+
+    let mut mode_select =
+        SelectView::<&'static str>::new()
+            .popup()
+            .on_submit(|s, mode: &&'static str| {
+                s.call_on_name("submode", |view: &mut SelectView<Option<&'static str>>| {
+                    view.clear();
+                    view.add_item("(none)", None);
+                    for submode in submodes_of(mode) {
+                        view.add_item(*submode, Some(*submode));
+                    }
+                });
+            });
+    mode_select.add_item(Mode::EYEBALL.mode(), Mode::EYEBALL.mode());
+    if mode == Mode::OTHER {
+        mode_select.add_item(Mode::OTHER.mode(), Mode::OTHER.mode());
     }
+    for (adif_mode, _) in ADIF_MODES {
+        mode_select.add_item(*adif_mode, *adif_mode);
+    }
+    let index = mode_select
+        .iter()
+        .position(|(_, item)| *item == mode.mode())
+        .unwrap_or_default();
+    let mode_select = mode_select.selected(index);
+
+    let mut submode_select = SelectView::<Option<&'static str>>::new().popup();
+    submode_select.add_item("(none)", None);
+    for submode in submodes_of(mode.mode()) {
+        submode_select.add_item(*submode, Some(*submode));
+    }
+    let index = submode_select
+        .iter()
+        .position(|(_, item)| *item == mode.submode())
+        .unwrap_or_default();
+    let submode_select = submode_select.selected(index);
 
     let mut widget = Dialog::text("Add qsl record.")
         .title("Add QSL Record")
@@ -196,10 +526,27 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                     "Callsign",
                     EditView::new()
                         .content(call_number)
+                        .on_edit(move |s, call_number, _| {
+                            // New records get what is remembered of the station.
+                            if !is_new {
+                                return;
+                            }
+                            let contact = s
+                                .user_data::<QSLManager>()
+                                .unwrap()
+                                .context
+                                .get_contact(&Callsign::base_of(call_number));
+                            match contact {
+                                Ok(Some(contact)) => fill_contact(s, &contact),
+                                Ok(None) => {}
+                                Err(e) => log::warn!("{e}"),
+                            }
+                        })
                         .with_name("call_number"),
                 )
+                .child("Station", station_select.with_name("station_profile"))
                 .child(
-                    "Datetime",
+                    &format!("Datetime ({time_zone})"),
                     LinearLayout::horizontal()
                         .child(
                             EditView::new()
@@ -232,19 +579,94 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                         ),
                 )
                 .child(
-                    "Mode",
-                    SelectView::<Mode>::new()
-                        .popup()
-                        .item(Mode::EYEBALL.to_string(), Mode::EYEBALL)
-                        .item(Mode::FM.to_string(), Mode::FM)
-                        .item(Mode::SSB.to_string(), Mode::SSB)
-                        .item(Mode::CW.to_string(), Mode::CW)
-                        .item(Mode::FTB.to_string(), Mode::FTB)
-                        .item(Mode::OTHER.to_string(), Mode::OTHER)
-                        .selected(mode as usize)
-                        .with_name("mode"),
+                    "End (optional)",
+                    LinearLayout::horizontal()
+                        .child(
+                            EditView::new()
+                                .content(year_off)
+                                .with_name("year_off")
+                                .fixed_width(5),
+                        )
+                        .child(TextView::new("-"))
+                        .child(
+                            EditView::new()
+                                .content(month_off)
+                                .with_name("month_off")
+                                .fixed_width(3),
+                        )
+                        .child(TextView::new("-"))
+                        .child(
+                            EditView::new()
+                                .content(day_off)
+                                .with_name("day_off")
+                                .fixed_width(3),
+                        )
+                        .child(TextView::new(" "))
+                        .child(
+                            EditView::new()
+                                .content(hour_off)
+                                .with_name("hour_off")
+                                .fixed_width(3),
+                        )
+                        .child(TextView::new(":"))
+                        .child(
+                            EditView::new()
+                                .content(minute_off)
+                                .with_name("minute_off")
+                                .fixed_width(3),
+                        ),
                 )
+                .child("Mode", mode_select.with_name("mode"))
+                .child("Submode", submode_select.with_name("submode"))
                 .child("Frequency", EditView::new().content(freq).with_name("freq"))
+                .child(
+                    "Satellite",
+                    EditView::new().content(sat_name).with_name("sat_name"),
+                )
+                .child(
+                    "Satellite Mode",
+                    EditView::new().content(sat_mode).with_name("sat_mode"),
+                )
+                .child(
+                    "Contest",
+                    EditView::new()
+                        .content(contest_id)
+                        .on_edit(move |s, contest_id, _| {
+                            // New contest records get the next serial of their contest.
+                            if !is_new {
+                                return;
+                            }
+                            let serial = match contest_id.trim() {
+                                "" => Ok("".to_string()),
+                                contest_id => s
+                                    .user_data::<QSLManager>()
+                                    .unwrap()
+                                    .context
+                                    .get_next_serial(&contest_id.to_uppercase())
+                                    .map(|n| n.to_string()),
+                            };
+                            match serial {
+                                Ok(serial) => {
+                                    s.call_on_name("stx", |view: &mut EditView| {
+                                        view.set_content(serial)
+                                    });
+                                }
+                                Err(e) => log::warn!("{e}"),
+                            }
+                        })
+                        .with_name("contest_id"),
+                )
+                .child("Serial (sent)", EditView::new().content(stx).with_name("stx"))
+                .child(
+                    "Exchange (sent)",
+                    EditView::new().content(stx_string).with_name("stx_string"),
+                )
+                .child("Serial (rcvd)", EditView::new().content(srx).with_name("srx"))
+                .child(
+                    "Exchange (rcvd)",
+                    EditView::new().content(srx_string).with_name("srx_string"),
+                )
+                .child("Equipment preset", preset_select)
                 .child(
                     "RST (Me)",
                     EditView::new().content(rst_me).with_name("rst_me"),
@@ -253,6 +675,16 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                     "QTH (Me)",
                     EditView::new().content(qth_me).with_name("qth_me"),
                 )
+                .child(
+                    "Grid (Me)",
+                    EditView::new().content(grid_me).with_name("grid_me"),
+                )
+                .child(
+                    "Position (Me)",
+                    EditView::new()
+                        .content(position_me)
+                        .with_name("position_me"),
+                )
                 .child(
                     "Rig (Me)",
                     EditView::new().content(rig_me).with_name("rig_me"),
@@ -277,6 +709,22 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                         .content(qth_counterpart)
                         .with_name("qth_counterpart"),
                 )
+                .child(
+                    "State (Counterpart)",
+                    EditView::new().content(state).with_name("state"),
+                )
+                .child(
+                    "Grid (Counterpart)",
+                    EditView::new()
+                        .content(grid_counterpart)
+                        .with_name("grid_counterpart"),
+                )
+                .child(
+                    "Position (Counterpart)",
+                    EditView::new()
+                        .content(position_counterpart)
+                        .with_name("position_counterpart"),
+                )
                 .child(
                     "Rig (Counterpart)",
                     EditView::new()
@@ -295,6 +743,39 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                         .content(ant_counterpart)
                         .with_name("ant_counterpart"),
                 )
+                .child(
+                    "Name (Counterpart)",
+                    EditView::new()
+                        .content(name_counterpart)
+                        .with_name("name_counterpart"),
+                )
+                .child(
+                    "Email (Counterpart)",
+                    EditView::new()
+                        .content(email_counterpart)
+                        .with_name("email_counterpart"),
+                )
+                .child(
+                    "Address (Counterpart)",
+                    EditView::new()
+                        .content(address_counterpart)
+                        .with_name("address_counterpart"),
+                )
+                .with(|list| {
+                    for (service, card) in QSLService::ALL.iter().zip(&cards) {
+                        add_card_rows(list, *service, card);
+                    }
+                })
+                .child(
+                    "Card route (sent)",
+                    route_select(qsl_sent_via).with_name("qsl_sent_via"),
+                )
+                .child(
+                    "Card route (rcvd)",
+                    route_select(qsl_rcvd_via).with_name("qsl_rcvd_via"),
+                )
+                .child("QSL via", EditView::new().content(qsl_via).with_name("qsl_via"))
+                .child("Tags", EditView::new().content(tags).with_name("tags"))
                 .child("Note", EditView::new().content(note).with_name("note"))
                 .scrollable(),
         )
@@ -312,6 +793,17 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                 );
                 return;
             }
+            let call_number = call_number.unwrap().trim().to_uppercase();
+            let call_number = match call_number.as_str() {
+                "NOCALL" => call_number,
+                _ => match Callsign::parse(&call_number) {
+                    Ok(callsign) => callsign.to_string(),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse callsign failed: {e}"));
+                        return;
+                    }
+                },
+            };
             log::debug!("Callsign initialized...");
 
             let year = match s
@@ -375,8 +867,11 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
             };
 
             let datetime = match chrono::NaiveDate::from_ymd_opt(year, month, day) {
-                Some(date) => match date.and_hms_opt(hour, minute, 0) {
-                    Some(date) => date,
+                Some(date) => match date
+                    .and_hms_opt(hour, minute, 0)
+                    .and_then(|date| time_zone.from_local_datetime(&date).single())
+                {
+                    Some(date) => date.with_timezone(&Utc),
                     None => {
                         show_error_dialog(s, "Invalid time, check hour and minute is valid?");
                         return;
@@ -387,6 +882,13 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                     return;
                 }
             };
+            let datetime_off = match read_datetime_off(s, &time_zone, &datetime) {
+                Ok(d) => d,
+                Err(e) => {
+                    show_error_dialog(s, &e);
+                    return;
+                }
+            };
             log::debug!("Datetime initialized...");
 
             let watt_me = match string_parser(
@@ -420,20 +922,206 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                 },
             };
 
-            let new_qsl = QSL {
+            let freq = match string_parser(
+                &s.call_on_name("freq", |view: &mut EditView| view.get_content().to_string())
+                    .unwrap(),
+            ) {
+                None => None,
+                Some(str) => match Frequency::parse(&str) {
+                    Ok(f) => Some(f),
+                    Err(e) => {
+                        show_error_dialog(
+                            s,
+                            &format!(
+                                "Parse freq failed: {e}\nWrite the frequency you listen on, then the shift or the frequency you transmit on and the tone, e.g. 438.500/-7.6/T88.5."
+                            ),
+                        );
+                        return;
+                    }
+                },
+            };
+            let grid_me = match string_parser(
+                &s.call_on_name("grid_me", |view: &mut EditView| {
+                    view.get_content().to_string()
+                })
+                .unwrap(),
+            ) {
+                None => None,
+                Some(str) => match normalize_grid(&str) {
+                    Ok(g) => Some(g),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse grid_me failed: {e}"));
+                        return;
+                    }
+                },
+            };
+            let grid_counterpart = match string_parser(
+                &s.call_on_name("grid_counterpart", |view: &mut EditView| {
+                    view.get_content().to_string()
+                })
+                .unwrap(),
+            ) {
+                None => None,
+                Some(str) => match normalize_grid(&str) {
+                    Ok(g) => Some(g),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse grid_counterpart failed: {e}"));
+                        return;
+                    }
+                },
+            };
+
+            let position_me = match string_parser(
+                &s.call_on_name("position_me", |view: &mut EditView| {
+                    view.get_content().to_string()
+                })
+                .unwrap(),
+            ) {
+                None => None,
+                Some(str) => match parse_coordinates(&str) {
+                    Ok(p) => Some(p),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse position_me failed: {e}"));
+                        return;
+                    }
+                },
+            };
+            let position_counterpart = match string_parser(
+                &s.call_on_name("position_counterpart", |view: &mut EditView| {
+                    view.get_content().to_string()
+                })
+                .unwrap(),
+            ) {
+                None => None,
+                Some(str) => match parse_coordinates(&str) {
+                    Ok(p) => Some(p),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse position_counterpart failed: {e}"));
+                        return;
+                    }
+                },
+            };
+
+            let mut cards = Vec::new();
+            for service in QSLService::ALL {
+                match read_card(s, service) {
+                    Ok(card) => cards.push(card),
+                    Err(e) => {
+                        show_error_dialog(s, &e);
+                        return;
+                    }
+                }
+            }
+            let [card_paper, card_eqsl, card_lotw]: [QSLCard; 3] = cards.try_into().unwrap();
+            let qsl_sent_via = s
+                .call_on_name("qsl_sent_via", |view: &mut SelectView<Option<QSLRoute>>| {
+                    *view.selection().unwrap()
+                })
+                .unwrap();
+            let qsl_rcvd_via = s
+                .call_on_name("qsl_rcvd_via", |view: &mut SelectView<Option<QSLRoute>>| {
+                    *view.selection().unwrap()
+                })
+                .unwrap();
+
+            let mut serials = [None, None];
+            for (serial, name) in serials.iter_mut().zip(["stx", "srx"]) {
+                *serial = match string_parser(
+                    &s.call_on_name(name, |view: &mut EditView| view.get_content().to_string())
+                        .unwrap(),
+                ) {
+                    None => None,
+                    Some(str) => match str.parse::<u32>() {
+                        Ok(n) => Some(n),
+                        Err(e) => {
+                            show_error_dialog(s, &format!("Parse {name} failed: {e}."));
+                            return;
+                        }
+                    },
+                };
+            }
+            let [stx, srx] = serials;
+
+            let station_profile = s
+                .call_on_name("station_profile", |view: &mut SelectView<i32>| {
+                    view.selection().map(|id| *id)
+                })
+                .unwrap();
+
+            let mode = s
+                .call_on_name("mode", |view: &mut SelectView<&'static str>| {
+                    *view.selection().unwrap()
+                })
+                .unwrap();
+            let submode = s
+                .call_on_name("submode", |view: &mut SelectView<Option<&'static str>>| {
+                    *view.selection().unwrap()
+                })
+                .unwrap();
+            let mode = match Mode::new(mode, submode) {
+                Ok(m) => m,
+                Err(e) => {
+                    show_error_dialog(s, &format!("Parse mode failed: {e}"));
+                    return;
+                }
+            };
+
+            // Comma separated, each tag once.
+            let mut tags = Vec::<String>::new();
+            let tags_text = s
+                .call_on_name("tags", |view: &mut EditView| view.get_content().to_string())
+                .unwrap();
+            for tag in tags_text.split(',').map(str::trim) {
+                if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+
+            let state = s
+                .call_on_name("state", |view: &mut EditView| view.get_content().to_string())
+                .unwrap();
+            let state = edited_state(&state, derived_state, &call_number);
+
+            let mut new_qsl = QSL {
                 id: id.clone(),
-                call_number: call_number.unwrap().clone(),
+                call_number,
 
-                mode: s
-                    .call_on_name("mode", |view: &mut SelectView<Mode>| {
-                        view.selection().unwrap().clone().deref().clone()
+                mode,
+                freq,
+                datetime,
+                datetime_off,
+                card_paper,
+                card_eqsl,
+                card_lotw,
+                qsl_sent_via,
+                qsl_rcvd_via,
+                station_profile,
+                contest_id: string_parser(
+                    &s.call_on_name("contest_id", |view: &mut EditView| {
+                        view.get_content().trim().to_uppercase()
+                    })
+                    .unwrap(),
+                ),
+                stx,
+                srx,
+                stx_string: string_parser(
+                    &s.call_on_name("stx_string", |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap(),
+                ),
+                srx_string: string_parser(
+                    &s.call_on_name("srx_string", |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap(),
+                ),
+                qsl_via: string_parser(
+                    &s.call_on_name("qsl_via", |view: &mut EditView| {
+                        view.get_content().to_string()
                     })
                     .unwrap(),
-                freq: string_parser(
-                    &s.call_on_name("freq", |view: &mut EditView| view.get_content().to_string())
-                        .unwrap(),
                 ),
-                datetime,
                 rst_me: string_parser(
                     &s.call_on_name("rst_me", |view: &mut EditView| {
                         view.get_content().to_string()
@@ -484,45 +1172,90 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                     })
                     .unwrap(),
                 ),
+                name_counterpart: string_parser(
+                    &s.call_on_name("name_counterpart", |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap(),
+                ),
+                email_counterpart: string_parser(
+                    &s.call_on_name("email_counterpart", |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap(),
+                ),
+                address_counterpart: string_parser(
+                    &s.call_on_name("address_counterpart", |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap(),
+                ),
                 note: string_parser(
                     &s.call_on_name("note", |view: &mut EditView| view.get_content().to_string())
                         .unwrap(),
                 ),
-            };
-            log::debug!("Adding qsl record...");
-
-            if let Some(qslmanager) = s.user_data::<QSLManager>() {
-                log::debug!("Database connected...");
-                if is_new {
-                    match qslmanager.context.add_qsl(new_qsl) {
-                        Ok(_) => {
-                            log::debug!("QSL record added.");
-                            qslmanager.fetch_shown_qsl();
-                            s.pop_layer();
-                            s.pop_layer();
-                            show_qsl_table(s);
-                            show_error_dialog(s, "QSL record added.");
-                        }
+                sat_name: string_parser(
+                    &s.call_on_name("sat_name", |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap(),
+                ),
+                sat_mode: string_parser(
+                    &s.call_on_name("sat_mode", |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap(),
+                ),
+                grid_me,
+                grid_counterpart,
+                lat_me: position_me.map(|(lat, _)| lat),
+                lon_me: position_me.map(|(_, lon)| lon),
+                lat_counterpart: position_counterpart.map(|(lat, _)| lat),
+                lon_counterpart: position_counterpart.map(|(_, lon)| lon),
+                dxcc,
+                country: country.clone(),
+                cont: cont.clone(),
+                cqz,
+                ituz,
+                state,
+            };
+            log::debug!("Adding qsl record...");
+
+            if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                log::debug!("Database connected...");
+                qslmanager.resolve_country(&mut new_qsl);
+                let dupes = if is_new {
+                    match qslmanager.find_dupes(&new_qsl) {
+                        Ok(dupes) => dupes,
                         Err(e) => {
-                            log::debug!("QSL record not added with error: {e}");
-                            show_error_dialog(s, &format!("Failed to add qsl record: {}", e));
+                            show_error_dialog(s, &format!("Failed to check dupes: {e}"));
+                            return;
                         }
                     }
                 } else {
-                    match qslmanager.context.update(new_qsl) {
-                        Ok(_) => {
-                            log::debug!("QSL record {id} updated.");
-                            qslmanager.fetch_shown_qsl();
-                            s.pop_layer();
-                            s.pop_layer();
-                            show_qsl_table(s);
-                            show_error_dialog(s, &format!("QSL record {id} updated."));
-                        }
-                        Err(e) => {
-                            log::debug!("QSL record {id} could not updated with error: {e}");
-                            show_error_dialog(s, &format!("Failed to add update record {id}: {e}"));
-                        }
+                    Vec::new()
+                };
+                match dupes.first() {
+                    Some(dupe) => {
+                        let message = format!(
+                            "{} was worked as {} on {} in the same band and mode.\nLog it anyway?",
+                            Callsign::base_of(&new_qsl.call_number),
+                            dupe.call_number,
+                            dupe.local_datetime(&time_zone).format("%Y-%m-%d %H:%M")
+                        );
+                        s.add_layer(
+                            Dialog::text(message)
+                                .title("Dupe")
+                                .button("No", |s| {
+                                    s.pop_layer();
+                                })
+                                .button("Yes", move |s| {
+                                    s.pop_layer();
+                                    save_record(s, new_qsl.clone(), &tags, is_new);
+                                }),
+                        );
                     }
+                    None => save_record(s, new_qsl, &tags, is_new),
                 }
             } else {
                 log::error!("Database not connected while trying to write record to database.");
@@ -531,9 +1264,11 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
         });
 
     if !is_new {
+        widget.add_button("Attachments", move |s| attachments_dialog(s, id));
+        widget.add_button("History", move |s| history_dialog(s, Some(id)));
         widget.add_button("Delete", move |s| {
             s.add_layer(
-                Dialog::text("Are you sure?")
+                Dialog::text("Move this record to the trash?")
                     .title("Confirm deletion")
                     .button("No", |s| {
                         s.pop_layer();
@@ -541,13 +1276,17 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
                     .button("Yes", move |s| {
                         if let Some(qslmanager) = s.user_data::<QSLManager>() {
                             log::debug!("Database connected...");
-                            match qslmanager.context.delete(id) {
+                            match qslmanager.delete_qsl(id) {
                                 Ok(_) => {
-                                    log::debug!("QSL record {id} deleted.");
+                                    log::debug!("QSL record {id} moved to trash.");
+                                    qslmanager.fetch_shown_qsl();
                                     s.pop_layer();
                                     s.pop_layer();
                                     show_qsl_table(s);
-                                    show_error_dialog(s, &format!("QSL record {id} deleted."));
+                                    show_error_dialog(
+                                        s,
+                                        &format!("QSL record {id} moved to trash."),
+                                    );
                                 }
                                 Err(e) => {
                                     log::debug!(
@@ -575,12 +1314,1397 @@ pub fn edit_record_dialog(s: &mut Cursive, qsl: Option<QSL>) {
     }));
 }
 
+pub fn station_profiles_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let active = qslmanager.station().id;
+    let stations = match qslmanager.context.get_station_profiles() {
+        Ok(stations) => stations,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read station profiles: {e}"));
+            return;
+        }
+    };
+
+    let mut list = SelectView::<StationProfile>::new();
+    for station in stations {
+        let marker = if station.id == active { "*" } else { " " };
+        list.add_item(format!("{marker} {station}"), station);
+    }
+    let index = list
+        .iter()
+        .position(|(_, station)| station.id == active)
+        .unwrap_or_default();
+    let list = list
+        .selected(index)
+        .on_submit(|s, station: &StationProfile| edit_station_dialog(s, Some(station.clone())));
+
+    s.add_layer(
+        Dialog::around(list.with_name("station_list").min_width(40))
+            .title("Station profiles (* active)")
+            .button("Use", |s| {
+                let Some(id) = selected_station(s).map(|station| station.id) else {
+                    return;
+                };
+                if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                    match qslmanager.set_active_station(id) {
+                        Ok(_) => {
+                            s.pop_layer();
+                            show_qsl_table(s);
+                        }
+                        Err(e) => show_error_dialog(s, &format!("Failed to switch station: {e}")),
+                    }
+                }
+            })
+            .button("New", |s| edit_station_dialog(s, None))
+            .button("Edit", |s| {
+                if let Some(station) = selected_station(s) {
+                    edit_station_dialog(s, Some(station));
+                }
+            })
+            .button("Delete", |s| {
+                let Some(station) = selected_station(s) else {
+                    return;
+                };
+                if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                    match qslmanager.delete_station(station.id) {
+                        Ok(_) => {
+                            s.pop_layer();
+                            station_profiles_dialog(s);
+                        }
+                        Err(e) => show_error_dialog(s, &format!("Failed to delete station: {e}")),
+                    }
+                }
+            })
+            .button("Close", |s| {
+                s.pop_layer();
+            }),
+    );
+
+    fn selected_station(s: &mut Cursive) -> Option<StationProfile> {
+        s.call_on_name("station_list", |view: &mut SelectView<StationProfile>| {
+            view.selection().map(|station| (*station).clone())
+        })
+        .flatten()
+    }
+}
+
+fn edit_station_dialog(s: &mut Cursive, station: Option<StationProfile>) {
+    let is_new = station.is_none();
+    let station = station.unwrap_or(StationProfile {
+        id: 0,
+        callsign: "".to_string(),
+        operator: None,
+        qth: None,
+        grid: None,
+        rig: None,
+        ant: None,
+        watt: None,
+    });
+    let id = station.id;
+
+    s.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child(
+                    "Callsign",
+                    EditView::new()
+                        .content(station.callsign)
+                        .with_name("station_callsign"),
+                )
+                .child(
+                    "Operator",
+                    EditView::new()
+                        .content(station.operator.unwrap_or_default())
+                        .with_name("station_operator"),
+                )
+                .child(
+                    "QTH",
+                    EditView::new()
+                        .content(station.qth.unwrap_or_default())
+                        .with_name("station_qth"),
+                )
+                .child(
+                    "Grid",
+                    EditView::new()
+                        .content(station.grid.unwrap_or_default())
+                        .with_name("station_grid"),
+                )
+                .child(
+                    "Rig",
+                    EditView::new()
+                        .content(station.rig.unwrap_or_default())
+                        .with_name("station_rig"),
+                )
+                .child(
+                    "Antenna",
+                    EditView::new()
+                        .content(station.ant.unwrap_or_default())
+                        .with_name("station_ant"),
+                )
+                .child(
+                    "Watt",
+                    EditView::new()
+                        .content(station.watt.map_or("".to_string(), |w| w.to_string()))
+                        .with_name("station_watt"),
+                )
+                .min_width(50),
+        )
+        .title(if is_new {
+            "New station profile"
+        } else {
+            "Edit station profile"
+        })
+        .button("Submit", move |s| {
+            let read = |s: &mut Cursive, name: &str| {
+                string_parser(
+                    &s.call_on_name(name, |view: &mut EditView| {
+                        view.get_content().trim().to_string()
+                    })
+                    .unwrap(),
+                )
+            };
+
+            let callsign = match read(s, "station_callsign") {
+                Some(callsign) => callsign.to_uppercase(),
+                None => {
+                    show_error_dialog(s, "Callsign of the station cannot be empty.");
+                    return;
+                }
+            };
+            let grid = match read(s, "station_grid") {
+                None => None,
+                Some(str) => match normalize_grid(&str) {
+                    Ok(g) => Some(g),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse grid failed: {e}"));
+                        return;
+                    }
+                },
+            };
+            let watt = match read(s, "station_watt") {
+                None => None,
+                Some(str) => match str.parse::<f32>() {
+                    Ok(w) => Some(w),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse watt failed: {e}."));
+                        return;
+                    }
+                },
+            };
+            let station = StationProfile {
+                id,
+                callsign,
+                operator: read(s, "station_operator").map(|o| o.to_uppercase()),
+                qth: read(s, "station_qth"),
+                grid,
+                rig: read(s, "station_rig"),
+                ant: read(s, "station_ant"),
+                watt,
+            };
+
+            if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                let result = if is_new {
+                    qslmanager.context.add_station_profile(&station).map(|_| ())
+                } else {
+                    qslmanager.update_station(station)
+                };
+                match result {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        station_profiles_dialog(s);
+                    }
+                    Err(e) => show_error_dialog(s, &format!("Failed to save station: {e}")),
+                }
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+/// Files kept with record `qsl_id`, e.g. card scans and photos.
+fn attachments_dialog(s: &mut Cursive, qsl_id: i32) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let attachments = match qslmanager.context.get_attachments(qsl_id) {
+        Ok(attachments) => attachments,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read attachments: {e}"));
+            return;
+        }
+    };
+
+    let mut list = SelectView::<i32>::new();
+    for attachment in attachments {
+        list.add_item(attachment.to_string(), attachment.id);
+    }
+
+    s.add_layer(
+        Dialog::around(
+            list.with_name("attachment_list")
+                .min_height(3)
+                .min_width(40),
+        )
+        .title(format!("Attachments of record {qsl_id}"))
+        .button("Attach", move |s| attach_file_dialog(s, qsl_id))
+        .button("Detach", move |s| {
+            let Some(id) = s
+                .call_on_name("attachment_list", |view: &mut SelectView<i32>| {
+                    view.selection().map(|id| *id)
+                })
+                .flatten()
+            else {
+                return;
+            };
+            s.add_layer(
+                Dialog::text("Detach this file? It is removed from the logbook.")
+                    .title("Confirm detach")
+                    .button("No", |s| {
+                        s.pop_layer();
+                    })
+                    .button("Yes", move |s| {
+                        let qslmanager = s.user_data::<QSLManager>().unwrap();
+                        match qslmanager.context.delete_attachment(id) {
+                            Ok(_) => {
+                                s.pop_layer();
+                                s.pop_layer();
+                                attachments_dialog(s, qsl_id);
+                            }
+                            Err(e) => show_error_dialog(s, &e),
+                        }
+                    }),
+            );
+        })
+        .button("Close", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+/// Records moved to the trash, to restore or purge them.
+pub fn trash_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let time_zone = *qslmanager.time_zone();
+    let trash = match qslmanager.context.get_trash() {
+        Ok(trash) => trash,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read trash: {e}"));
+            return;
+        }
+    };
+    let retention = match qslmanager.context.get_trash_retention() {
+        Ok(Some(days)) => format!("Records are purged {days} day(s) after deletion."),
+        Ok(None) => "Records are kept until purged.".to_string(),
+        Err(e) => e,
+    };
+
+    let mut list = SelectView::<i32>::new();
+    for (qsl, deleted_at) in trash {
+        list.add_item(
+            format!(
+                "{} {} {} (deleted {})",
+                qsl.datetime
+                    .with_timezone(&time_zone)
+                    .format("%Y-%m-%d %H:%M"),
+                qsl.call_number,
+                qsl.mode,
+                deleted_at
+                    .with_timezone(&time_zone)
+                    .format("%Y-%m-%d %H:%M")
+            ),
+            qsl.id,
+        );
+    }
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(format!("{retention}\n ")))
+                .child(list.with_name("trash_list").scrollable().min_height(3)),
+        )
+        .title("Trash")
+        .button("Restore", |s| {
+            let Some(id) = selected_trash(s) else {
+                return;
+            };
+            let qslmanager = s.user_data::<QSLManager>().unwrap();
+            match qslmanager.context.restore(id) {
+                Ok(_) => {
+                    log::debug!("QSL record {id} restored.");
+                    qslmanager.fetch_shown_qsl();
+                    s.pop_layer();
+                    show_qsl_table(s);
+                    trash_dialog(s);
+                }
+                Err(e) => show_error_dialog(s, &e),
+            }
+        })
+        .button("Purge", |s| {
+            let Some(id) = selected_trash(s) else {
+                return;
+            };
+            s.add_layer(
+                Dialog::text("Purge this record? It cannot be restored from the trash.")
+                    .title("Confirm purge")
+                    .button("No", |s| {
+                        s.pop_layer();
+                    })
+                    .button("Yes", move |s| {
+                        let qslmanager = s.user_data::<QSLManager>().unwrap();
+                        match qslmanager.context.purge(id) {
+                            Ok(_) => {
+                                log::debug!("QSL record {id} purged.");
+                                s.pop_layer();
+                                s.pop_layer();
+                                trash_dialog(s);
+                            }
+                            Err(e) => show_error_dialog(s, &e),
+                        }
+                    }),
+            );
+        })
+        .button("Close", |s| {
+            s.pop_layer();
+        }),
+    );
+
+    fn selected_trash(s: &mut Cursive) -> Option<i32> {
+        s.call_on_name("trash_list", |view: &mut SelectView<i32>| {
+            view.selection().map(|id| *id)
+        })
+        .flatten()
+    }
+}
+
+pub fn trash_retention_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let days = qslmanager
+        .context
+        .get_trash_retention()
+        .unwrap_or_default()
+        .map(|days| days.to_string())
+        .unwrap_or_default();
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "Trashed records are purged after this many days.\n\
+                     Leave it empty to keep them until purged by hand.\n ",
+                ))
+                .child(ListView::new().child(
+                    "Days",
+                    EditView::new().content(days).with_name("trash_retention"),
+                )),
+        )
+        .title("Trash retention")
+        .button("Submit", |s| {
+            let input = s
+                .call_on_name("trash_retention", |view: &mut EditView| {
+                    view.get_content().trim().to_string()
+                })
+                .unwrap();
+            let days = match input.as_str() {
+                "" => None,
+                input => match input.parse::<u32>() {
+                    Ok(days) => Some(days),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse days failed: {e}"));
+                        return;
+                    }
+                },
+            };
+
+            let qslmanager = s.user_data::<QSLManager>().unwrap();
+            match qslmanager.set_trash_retention(days) {
+                Ok(purged) => {
+                    log::debug!("Trash retention set to {days:?}, {purged} record(s) purged.");
+                    s.pop_layer();
+                    if purged > 0 {
+                        s.add_layer(Dialog::info(format!(
+                            "{purged} record(s) purged from the trash."
+                        )));
+                    }
+                }
+                Err(e) => show_error_dialog(s, &format!("Failed to set trash retention: {e}")),
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+/// Changes of one record, or of every record so purged ones can be found.
+pub fn history_dialog(s: &mut Cursive, qsl_id: Option<i32>) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let time_zone = *qslmanager.time_zone();
+    let entries = match qslmanager.context.get_history(qsl_id) {
+        Ok(entries) => entries,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read history: {e}"));
+            return;
+        }
+    };
+
+    let mut list = SelectView::<HistoryEntry>::new()
+        .on_submit(move |s, entry| history_entry_dialog(s, entry.clone(), qsl_id.is_some()));
+    for entry in entries {
+        list.add_item(entry.summary(&time_zone), entry);
+    }
+
+    s.add_layer(
+        Dialog::around(list.scrollable().min_height(3).min_width(50))
+            .title(match qsl_id {
+                Some(id) => format!("History of record {id}"),
+                None => "History".to_string(),
+            })
+            .button("Close", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+/// Fields one change touched, and going back to the record before it.
+/// `from_record` tells the history was opened from the record's edit dialog,
+/// which is closed too after reverting as it shows the old values.
+fn history_entry_dialog(s: &mut Cursive, entry: HistoryEntry, from_record: bool) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let time_zone = *qslmanager.time_zone();
+    let changes = match qslmanager.context.get_history_changes(&entry) {
+        Ok(changes) => changes,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read changes: {e}"));
+            return;
+        }
+    };
+
+    let mut text = entry.summary(&time_zone);
+    text.push('\n');
+    if changes.is_empty() {
+        text.push_str("\nNo field changed.");
+    }
+    for change in changes {
+        text.push_str(&format!("\n{change}"));
+    }
+
+    let mut dialog = Dialog::around(TextView::new(text).scrollable())
+        .title(format!("{} of record {}", entry.action, entry.qsl_id));
+    if entry.action != HistoryAction::Insert {
+        dialog.add_button("Revert", move |s| {
+            s.add_layer(
+                Dialog::text(format!(
+                    "Put record {} back to how it was before this change?",
+                    entry.qsl_id
+                ))
+                .title("Confirm revert")
+                .button("No", |s| {
+                    s.pop_layer();
+                })
+                .button("Yes", {
+                    let entry = entry.clone();
+                    move |s| {
+                        let qslmanager = s.user_data::<QSLManager>().unwrap();
+                        match qslmanager.context.revert(&entry) {
+                            Ok(_) => {
+                                log::debug!("QSL record {} reverted.", entry.qsl_id);
+                                qslmanager.fetch_shown_qsl();
+                                s.pop_layer();
+                                s.pop_layer();
+                                s.pop_layer();
+                                if from_record {
+                                    s.pop_layer();
+                                }
+                                show_qsl_table(s);
+                                show_error_dialog(
+                                    s,
+                                    &format!("QSL record {} reverted.", entry.qsl_id),
+                                );
+                            }
+                            Err(e) => show_error_dialog(s, &e),
+                        }
+                    }
+                }),
+            );
+        });
+    }
+
+    s.add_layer(dialog.button("Close", |s| {
+        s.pop_layer();
+    }));
+}
+
+fn attach_file_dialog(s: &mut Cursive, qsl_id: i32) {
+    s.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child("File path", EditView::new().with_name("attachment_path"))
+                .min_width(50),
+        )
+        .title("Attach file")
+        .button("Attach", move |s| {
+            let path = s
+                .call_on_name("attachment_path", |view: &mut EditView| {
+                    view.get_content().trim().to_string()
+                })
+                .unwrap();
+            if path.is_empty() {
+                show_error_dialog(s, "File path is empty.");
+                return;
+            }
+            let qslmanager = s.user_data::<QSLManager>().unwrap();
+            match qslmanager.attach_file(qsl_id, Path::new(&path)) {
+                Ok(_) => {
+                    s.pop_layer();
+                    s.pop_layer();
+                    attachments_dialog(s, qsl_id);
+                }
+                Err(e) => show_error_dialog(s, &e),
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+pub fn equipment_presets_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let prefill_from_last = qslmanager
+        .context
+        .get_prefill_from_last()
+        .unwrap_or_default();
+    let presets = match qslmanager.context.get_equipment_presets() {
+        Ok(presets) => presets,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read equipment presets: {e}"));
+            return;
+        }
+    };
+
+    let mut list = SelectView::<EquipmentPreset>::new()
+        .on_submit(|s, preset: &EquipmentPreset| edit_preset_dialog(s, Some(preset.clone())));
+    for preset in presets {
+        list.add_item(preset.name.clone(), preset);
+    }
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(list.with_name("preset_list").min_height(3))
+                .child(
+                    LinearLayout::horizontal()
+                        .child(Checkbox::new().with_checked(prefill_from_last).on_change(
+                            |s, checked| {
+                                if let Some(qslmanager) = s.user_data::<QSLManager>()
+                                    && let Err(e) =
+                                        qslmanager.context.set_prefill_from_last(checked)
+                                {
+                                    show_error_dialog(s, &e);
+                                }
+                            },
+                        ))
+                        .child(TextView::new(" New records use the last QSO's equipment")),
+                )
+                .min_width(40),
+        )
+        .title("Equipment presets")
+        .button("New", |s| edit_preset_dialog(s, None))
+        .button("Edit", |s| {
+            if let Some(preset) = selected_preset(s) {
+                edit_preset_dialog(s, Some(preset));
+            }
+        })
+        .button("Delete", |s| {
+            let Some(preset) = selected_preset(s) else {
+                return;
+            };
+            if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                match qslmanager.context.delete_equipment_preset(preset.id) {
+                    Ok(_) => {
+                        s.pop_layer();
+                        equipment_presets_dialog(s);
+                    }
+                    Err(e) => show_error_dialog(s, &format!("Failed to delete preset: {e}")),
+                }
+            }
+        })
+        .button("Close", |s| {
+            s.pop_layer();
+        }),
+    );
+
+    fn selected_preset(s: &mut Cursive) -> Option<EquipmentPreset> {
+        s.call_on_name("preset_list", |view: &mut SelectView<EquipmentPreset>| {
+            view.selection().map(|preset| (*preset).clone())
+        })
+        .flatten()
+    }
+}
+
+fn edit_preset_dialog(s: &mut Cursive, preset: Option<EquipmentPreset>) {
+    let is_new = preset.is_none();
+    let preset = preset.unwrap_or(EquipmentPreset {
+        id: 0,
+        name: "".to_string(),
+        qth: None,
+        rig: None,
+        ant: None,
+        watt: None,
+    });
+    let id = preset.id;
+
+    s.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child(
+                    "Name",
+                    EditView::new()
+                        .content(preset.name)
+                        .with_name("preset_name"),
+                )
+                .child(
+                    "QTH",
+                    EditView::new()
+                        .content(preset.qth.unwrap_or_default())
+                        .with_name("preset_qth"),
+                )
+                .child(
+                    "Rig",
+                    EditView::new()
+                        .content(preset.rig.unwrap_or_default())
+                        .with_name("preset_rig"),
+                )
+                .child(
+                    "Antenna",
+                    EditView::new()
+                        .content(preset.ant.unwrap_or_default())
+                        .with_name("preset_ant"),
+                )
+                .child(
+                    "Watt",
+                    EditView::new()
+                        .content(preset.watt.map_or("".to_string(), |w| w.to_string()))
+                        .with_name("preset_watt"),
+                )
+                .min_width(50),
+        )
+        .title(if is_new {
+            "New equipment preset"
+        } else {
+            "Edit equipment preset"
+        })
+        .button("Submit", move |s| {
+            let read = |s: &mut Cursive, name: &str| {
+                string_parser(
+                    &s.call_on_name(name, |view: &mut EditView| {
+                        view.get_content().trim().to_string()
+                    })
+                    .unwrap(),
+                )
+            };
+
+            let name = match read(s, "preset_name") {
+                Some(name) => name,
+                None => {
+                    show_error_dialog(s, "Name of the preset cannot be empty.");
+                    return;
+                }
+            };
+            let watt = match read(s, "preset_watt") {
+                None => None,
+                Some(str) => match str.parse::<f32>() {
+                    Ok(w) => Some(w),
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Parse watt failed: {e}."));
+                        return;
+                    }
+                },
+            };
+            let preset = EquipmentPreset {
+                id,
+                name,
+                qth: read(s, "preset_qth"),
+                rig: read(s, "preset_rig"),
+                ant: read(s, "preset_ant"),
+                watt,
+            };
+
+            if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                let result = if is_new {
+                    qslmanager.context.add_equipment_preset(&preset)
+                } else {
+                    qslmanager.context.update_equipment_preset(&preset)
+                };
+                match result {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        equipment_presets_dialog(s);
+                    }
+                    Err(e) => show_error_dialog(s, &format!("Failed to save preset: {e}")),
+                }
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+pub fn country_file_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let path = qslmanager
+        .context
+        .get_country_file()
+        .unwrap_or_default()
+        .unwrap_or_default();
+    let status = match qslmanager.country_file() {
+        Some(file) => format!("{} entities loaded.", file.entities()),
+        None => "No country file loaded.".to_string(),
+    };
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(format!(
+                    "DXCC entities and zones are resolved from a cty.dat or cty.csv\n\
+                     file, see https://www.country-files.com. {status}\n "
+                )))
+                .child(
+                    ListView::new()
+                        .child(
+                            "Country file",
+                            EditView::new().content(path).with_name("country_file"),
+                        )
+                        .child(
+                            "Resolve existing records",
+                            Checkbox::new().with_name("resolve_existing"),
+                        ),
+                )
+                .min_width(60),
+        )
+        .title("Country file")
+        .button("Submit", |s| {
+            let path = s
+                .call_on_name("country_file", |view: &mut EditView| {
+                    view.get_content().trim().to_string()
+                })
+                .unwrap();
+            let resolve_existing = s
+                .call_on_name("resolve_existing", |view: &mut Checkbox| view.is_checked())
+                .unwrap();
+
+            if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                let entities = match qslmanager.load_country_file(&path) {
+                    Ok(entities) => entities,
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Failed to load country file: {e}"));
+                        return;
+                    }
+                };
+                let message = if resolve_existing {
+                    match qslmanager.resolve_all_countries() {
+                        Ok(resolved) => {
+                            format!("{entities} entities loaded, {resolved} record(s) resolved.")
+                        }
+                        Err(e) => {
+                            show_error_dialog(s, &format!("Failed to resolve records: {e}"));
+                            return;
+                        }
+                    }
+                } else {
+                    format!("{entities} entities loaded.")
+                };
+                s.pop_layer();
+                s.add_layer(Dialog::info(message));
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+pub fn time_zone_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let time_zone = *qslmanager.time_zone();
+    let converted = match qslmanager.context.get_datetimes_converted() {
+        Ok(converted) => converted,
+        Err(e) => {
+            show_error_dialog(s, &e);
+            return;
+        }
+    };
+
+    let mut fields = ListView::new().child(
+        "Time zone",
+        EditView::new()
+            .content(time_zone.to_string())
+            .with_name("time_zone"),
+    );
+    // Converting shifts every record, so it is offered until it has been done once.
+    let text = match converted {
+        true => "Datetimes are stored in UTC and shown in this time zone.\n ",
+        false => {
+            fields.add_child(
+                "Convert existing records",
+                Checkbox::new().with_name("convert_existing"),
+            );
+            "Datetimes are stored in UTC and shown in this time zone.\n\
+             Tick the box if the records so far were logged in this zone\n\
+             rather than UTC. This can only be done once.\n "
+        }
+    };
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(text))
+                .child(fields),
+        )
+        .title("Time zone")
+        .button("Submit", |s| {
+            let input = s
+                .call_on_name("time_zone", |view: &mut EditView| {
+                    view.get_content().to_string()
+                })
+                .unwrap();
+            let time_zone = match parse_time_zone(&input) {
+                Ok(t) => t,
+                Err(e) => {
+                    show_error_dialog(s, &format!("Parse time zone failed: {e}"));
+                    return;
+                }
+            };
+            let convert_existing = s
+                .call_on_name("convert_existing", |view: &mut Checkbox| view.is_checked())
+                .unwrap_or(false);
+
+            if let Some(qslmanager) = s.user_data::<QSLManager>() {
+                match qslmanager.set_time_zone(time_zone, convert_existing) {
+                    Ok(_) => {
+                        log::debug!("Time zone set to {time_zone}.");
+                        s.pop_layer();
+                        show_qsl_table(s);
+                    }
+                    Err(e) => {
+                        show_error_dialog(s, &format!("Failed to set time zone: {e}"));
+                    }
+                }
+            } else {
+                log::error!("Database not connected while trying to write time zone to database.");
+                show_error_dialog(s, "Database error.");
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+pub fn statistics_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let records = qslmanager.tab_count(RecordKind::All).unwrap_or_default();
+    let stations = match qslmanager.context.get_station_count() {
+        Ok(stations) => stations,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read statistics: {e}"));
+            return;
+        }
+    };
+    match qslmanager.on_air_statistics(&QSLFilter::All) {
+        Ok(on_air) => s.add_layer(
+            Dialog::text(format!(
+                "Records: {records}\nStations: {stations}\nOn air: {}\nTimed contacts: {}\nLongest contact: {}",
+                fmt_duration(&on_air.total),
+                on_air.timed_contacts,
+                fmt_duration(&on_air.longest)
+            ))
+            .title("Statistics")
+            .button("OK", |s| {
+                s.pop_layer();
+            }),
+        ),
+        Err(e) => show_error_dialog(s, &format!("Failed to read statistics: {e}")),
+    }
+}
+
+pub fn provinces_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    match qslmanager.provinces_worked(&QSLFilter::All) {
+        Ok(provinces) => {
+            let worked = provinces.iter().filter(|(_, count)| *count > 0).count();
+            let mut text = format!("Worked {worked} / {}\n", provinces.len());
+            for (province, count) in provinces {
+                match count {
+                    0 => text.push_str(&format!("\n{province}: not worked")),
+                    _ => text.push_str(&format!("\n{province}: {count}")),
+                }
+            }
+            s.add_layer(
+                Dialog::around(TextView::new(text).scrollable())
+                    .title("Provinces worked")
+                    .button("OK", |s| {
+                        s.pop_layer();
+                    }),
+            )
+        }
+        Err(e) => show_error_dialog(s, &format!("Failed to read provinces: {e}")),
+    }
+}
+
+/// Sent and received rows of one service. Choosing Yes fills in today when the date is empty.
+fn add_card_rows(list: &mut ListView, service: QSLService, card: &QSLCard) {
+    let prefix = service.column_prefix();
+    for (direction, status, date) in [
+        ("sent", card.sent, card.sent_date),
+        ("rcvd", card.received, card.received_date),
+    ] {
+        let date_name = format!("{prefix}_{direction}_date");
+        let mut select = SelectView::<Option<QSLStatus>>::new().popup();
+        select.add_item("(none)", None);
+        for status in QSLStatus::ALL {
+            select.add_item(status.to_string(), Some(status));
+        }
+        let index = select
+            .iter()
+            .position(|(_, item)| *item == status)
+            .unwrap_or_default();
+        let name = date_name.clone();
+        let select = select
+            .selected(index)
+            .on_submit(move |s, status: &Option<QSLStatus>| {
+                if *status == Some(QSLStatus::Yes) {
+                    s.call_on_name(&name, |view: &mut EditView| {
+                        if view.get_content().is_empty() {
+                            view.set_content(Utc::now().date_naive().to_string());
+                        }
+                    });
+                }
+            });
+
+        list.add_child(
+            format!("{service} {direction}"),
+            LinearLayout::horizontal()
+                .child(select.with_name(format!("{prefix}_{direction}")))
+                .child(TextView::new(" on "))
+                .child(
+                    EditView::new()
+                        .content(date.map_or("".to_string(), |d| d.to_string()))
+                        .with_name(date_name)
+                        .fixed_width(11),
+                ),
+        );
+    }
+}
+
+fn route_select(route: Option<QSLRoute>) -> SelectView<Option<QSLRoute>> {
+    let mut select = SelectView::<Option<QSLRoute>>::new().popup();
+    select.add_item("(none)", None);
+    for route in QSLRoute::ALL {
+        select.add_item(route.to_string(), Some(route));
+    }
+    let index = select
+        .iter()
+        .position(|(_, item)| *item == route)
+        .unwrap_or_default();
+    select.selected(index)
+}
+
+/// Read the rows written by `add_card_rows` back.
+fn read_card(s: &mut Cursive, service: QSLService) -> Result<QSLCard, String> {
+    let prefix = service.column_prefix();
+    let mut status = Vec::new();
+    let mut date = Vec::new();
+    for direction in ["sent", "rcvd"] {
+        status.push(
+            s.call_on_name(
+                &format!("{prefix}_{direction}"),
+                |view: &mut SelectView<Option<QSLStatus>>| *view.selection().unwrap(),
+            )
+            .unwrap(),
+        );
+        let content = s
+            .call_on_name(
+                &format!("{prefix}_{direction}_date"),
+                |view: &mut EditView| view.get_content().to_string(),
+            )
+            .unwrap();
+        date.push(match string_parser(&content.trim().to_string()) {
+            None => None,
+            Some(d) => Some(
+                NaiveDate::parse_from_str(&d, "%Y-%m-%d")
+                    .map_err(|e| format!("Parse {service} {direction} date {d} failed: {e}"))?,
+            ),
+        });
+    }
+
+    Ok(QSLCard {
+        sent: status[0],
+        sent_date: date[0],
+        received: status[1],
+        received_date: date[1],
+    })
+}
+
+/// Read the optional end time of the dialog. All fields empty means no end time,
+/// an empty date means the start day, or the next day when the time is before the start.
+fn read_datetime_off(
+    s: &mut Cursive,
+    time_zone: &FixedOffset,
+    datetime: &DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, String> {
+    let mut fields = Vec::new();
+    for name in ["year_off", "month_off", "day_off", "hour_off", "minute_off"] {
+        let content = s
+            .call_on_name(name, |view: &mut EditView| view.get_content().to_string())
+            .unwrap();
+        fields.push(content.trim().to_string());
+    }
+    if fields.iter().all(|f| f.is_empty()) {
+        return Ok(None);
+    }
+
+    let hour = fields[3]
+        .parse::<u32>()
+        .map_err(|e| format!("Parse end hour failed: {e}"))?;
+    let minute = fields[4]
+        .parse::<u32>()
+        .map_err(|e| format!("Parse end minute failed: {e}"))?;
+    let time = chrono::NaiveTime::from_hms_opt(hour, minute, 0)
+        .ok_or("Invalid end time, check hour and minute is valid?")?;
+
+    let start = datetime.with_timezone(time_zone).naive_local();
+    let local = if fields[..3].iter().all(|f| f.is_empty()) {
+        let local = start.date().and_time(time);
+        if local < start {
+            local + TimeDelta::days(1)
+        } else {
+            local
+        }
+    } else {
+        let year = fields[0]
+            .parse::<i32>()
+            .map_err(|e| format!("Parse end year failed: {e}"))?;
+        let month = fields[1]
+            .parse::<u32>()
+            .map_err(|e| format!("Parse end month failed: {e}"))?;
+        let day = fields[2]
+            .parse::<u32>()
+            .map_err(|e| format!("Parse end day failed: {e}"))?;
+        chrono::NaiveDate::from_ymd_opt(year, month, day)
+            .ok_or("Invalid end date, check year, month and day is valid?")?
+            .and_time(time)
+    };
+    if local < start {
+        return Err("The end is before the start of the contact.".to_string());
+    }
+
+    match time_zone.from_local_datetime(&local).single() {
+        Some(d) => Ok(Some(d.with_timezone(&Utc))),
+        None => Err("Invalid end time, check hour and minute is valid?".to_string()),
+    }
+}
+
+/// Put the remembered details of a station into the edit dialog.
+fn fill_contact(s: &mut Cursive, contact: &Contact) {
+    for (name, value) in [
+        ("name_counterpart", &contact.name),
+        ("email_counterpart", &contact.email),
+        ("address_counterpart", &contact.address),
+        ("qsl_via", &contact.qsl_via),
+    ] {
+        if let Some(value) = value {
+            s.call_on_name(name, |view: &mut EditView| view.set_content(value.clone()));
+        }
+    }
+}
+
+/// Add or update a record from the edit dialog and go back to the table.
+fn save_record(s: &mut Cursive, qsl: QSL, tags: &[String], is_new: bool) {
+    let id = qsl.id;
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    if is_new {
+        match qslmanager.add_qsl(qsl, tags) {
+            Ok(_) => {
+                log::debug!("QSL record added.");
+                qslmanager.fetch_shown_qsl();
+                s.pop_layer();
+                s.pop_layer();
+                show_qsl_table(s);
+                show_error_dialog(s, "QSL record added.");
+            }
+            Err(e) => {
+                log::debug!("QSL record not added with error: {e}");
+                show_error_dialog(s, &format!("Failed to add qsl record: {}", e));
+            }
+        }
+    } else {
+        match qslmanager.update_qsl(qsl, tags) {
+            Ok(_) => {
+                log::debug!("QSL record {id} updated.");
+                qslmanager.fetch_shown_qsl();
+                s.pop_layer();
+                s.pop_layer();
+                show_qsl_table(s);
+                show_error_dialog(s, &format!("QSL record {id} updated."));
+            }
+            Err(e) => {
+                log::debug!("QSL record {id} could not updated with error: {e}");
+                show_error_dialog(s, &format!("Failed to add update record {id}: {e}"));
+            }
+        }
+    }
+}
+
+pub fn tag_filter_dialog(s: &mut Cursive) {
+    let qslmanager = s.user_data::<QSLManager>().unwrap();
+    let tags = match qslmanager.context.get_all_tags() {
+        Ok(tags) => tags,
+        Err(e) => {
+            show_error_dialog(s, &format!("Failed to read tags: {e}"));
+            return;
+        }
+    };
+    if tags.is_empty() {
+        show_error_dialog(s, "No record has a tag yet.");
+        return;
+    }
+
+    let mut list = SelectView::<String>::new().on_submit(|s, tag: &String| {
+        s.pop_layer();
+        filter_records(s, QSLFilter::Tag(tag.clone()));
+    });
+    for tag in tags {
+        list.add_item(tag.clone(), tag);
+    }
+    s.add_layer(
+        Dialog::around(list.scrollable().min_width(30))
+            .title("Filter by tag")
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn search_callsign_dialog(s: &mut Cursive) {
+    s.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child(
+                    "Callsign",
+                    EditView::new()
+                        .on_submit(|s, _| search_callsign(s))
+                        .with_name("search_callsign"),
+                )
+                .min_width(40),
+        )
+        .title("Search callsign")
+        .button("Search", search_callsign)
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+
+    /// Show the contacts of the base call, so BI9CKG/P finds BI9CKG too.
+    fn search_callsign(s: &mut Cursive) {
+        let input = s
+            .call_on_name("search_callsign", |view: &mut EditView| {
+                view.get_content().to_string()
+            })
+            .unwrap();
+        match Callsign::parse(&input) {
+            Ok(callsign) => {
+                s.pop_layer();
+                filter_records(s, QSLFilter::Callsign(callsign.base));
+            }
+            Err(e) => show_error_dialog(s, &format!("Parse callsign failed: {e}")),
+        }
+    }
+}
+
+/// Search view: every criterion that is filled in has to match, and the
+/// table pages through the result.
+pub fn search_dialog(s: &mut Cursive) {
+    let search = match s.user_data::<QSLManager>().unwrap().filter() {
+        QSLFilter::Search(search) => search.clone(),
+        _ => QSLSearch::default(),
+    };
+    let date = |d: Option<DateTime<FixedOffset>>| {
+        d.map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    };
+
+    let mut mode_select = SelectView::<Option<&'static str>>::new().popup();
+    mode_select.add_item("(any)", None);
+    mode_select.add_item(Mode::EYEBALL.mode(), Some(Mode::EYEBALL.mode()));
+    for (adif_mode, _) in ADIF_MODES {
+        mode_select.add_item(*adif_mode, Some(*adif_mode));
+    }
+    let index = mode_select
+        .iter()
+        .position(|(_, item)| item.as_deref() == search.mode.as_deref())
+        .unwrap_or_default();
+    let mode_select = mode_select.selected(index);
+
+    let mut band_select = SelectView::<Option<&'static str>>::new().popup();
+    band_select.add_item("(any)", None);
+    for (band, _, _) in BANDS {
+        band_select.add_item(band, Some(band));
+    }
+    let index = band_select
+        .iter()
+        .position(|(_, item)| item.as_deref() == search.band.as_deref())
+        .unwrap_or_default();
+    let band_select = band_select.selected(index);
+
+    let mut kind_select = SelectView::<RecordKind>::new().popup();
+    for kind in RecordKind::ALL {
+        kind_select.add_item(kind.to_string(), kind);
+    }
+    let index = kind_select
+        .iter()
+        .position(|(_, kind)| *kind == search.kind)
+        .unwrap_or_default();
+    let kind_select = kind_select.selected(index);
+
+    s.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child(
+                    "Callsign",
+                    EditView::new()
+                        .content(search.callsign.clone().unwrap_or_default())
+                        .on_submit(|s, _| submit_search(s))
+                        .with_name("search_callsign"),
+                )
+                .child(
+                    "Callsign starts with it",
+                    Checkbox::new()
+                        .with_checked(search.callsign_prefix)
+                        .with_name("search_callsign_prefix"),
+                )
+                .child("Mode", mode_select.with_name("search_mode"))
+                .child("Band", band_select.with_name("search_band"))
+                .child(
+                    "From (YYYY-MM-DD)",
+                    EditView::new()
+                        .content(date(search.from))
+                        .with_name("search_from"),
+                )
+                .child(
+                    "To (YYYY-MM-DD)",
+                    EditView::new()
+                        .content(date(search.to))
+                        .with_name("search_to"),
+                )
+                .child("Records", kind_select.with_name("search_kind"))
+                .child(
+                    "Note contains",
+                    EditView::new()
+                        .content(search.note.clone().unwrap_or_default())
+                        .on_submit(|s, _| submit_search(s))
+                        .with_name("search_note"),
+                )
+                .min_width(50),
+        )
+        .title("Search")
+        .button("Search", submit_search)
+        .button("Clear", |s| {
+            s.pop_layer();
+            filter_records(s, QSLFilter::All);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+
+    fn submit_search(s: &mut Cursive) {
+        let text = |s: &mut Cursive, name: &str| {
+            s.call_on_name(name, |view: &mut EditView| {
+                view.get_content().trim().to_string()
+            })
+            .and_then(|text| string_parser(&text))
+        };
+        let time_zone = *s.user_data::<QSLManager>().unwrap().time_zone();
+        let date = |text: Option<String>, name: &str| match text {
+            Some(text) => match NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+                Ok(date) => Ok(date
+                    .and_time(NaiveTime::MIN)
+                    .and_local_timezone(time_zone)
+                    .single()),
+                Err(e) => Err(format!("Parse {name} date {text} failed: {e}")),
+            },
+            None => Ok(None),
+        };
+
+        let callsign = text(s, "search_callsign").map(|c| c.to_uppercase());
+        let note = text(s, "search_note");
+        let from = date(text(s, "search_from"), "from");
+        let to = date(text(s, "search_to"), "to");
+        let (from, to) = match (from, to) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => {
+                show_error_dialog(s, &e);
+                return;
+            }
+        };
+        let search = QSLSearch {
+            callsign,
+            callsign_prefix: s
+                .call_on_name("search_callsign_prefix", |view: &mut Checkbox| {
+                    view.is_checked()
+                })
+                .unwrap(),
+            mode: s
+                .call_on_name(
+                    "search_mode",
+                    |view: &mut SelectView<Option<&'static str>>| view.selection(),
+                )
+                .flatten()
+                .and_then(|mode| mode.map(str::to_string)),
+            band: s
+                .call_on_name(
+                    "search_band",
+                    |view: &mut SelectView<Option<&'static str>>| view.selection(),
+                )
+                .flatten()
+                .and_then(|band| band.map(str::to_string)),
+            from,
+            to,
+            kind: s
+                .call_on_name("search_kind", |view: &mut SelectView<RecordKind>| {
+                    view.selection()
+                })
+                .flatten()
+                .map(|kind| *kind)
+                .unwrap_or_default(),
+            note,
+        };
+
+        s.pop_layer();
+        match search == QSLSearch::default() {
+            true => filter_records(s, QSLFilter::All),
+            false => filter_records(s, QSLFilter::Search(search)),
+        }
+    }
+}
+
 fn show_error_dialog(s: &mut Cursive, msg: &str) {
     s.add_layer(Dialog::text(msg).title("Error").button("OK", |s| {
         s.pop_layer();
     }));
 }
 
+fn submodes_of(mode: &str) -> &'static [&'static str] {
+    ADIF_MODES
+        .iter()
+        .find(|(m, _)| *m == mode)
+        .map_or(&[], |(_, submodes)| submodes)
+}
+
 fn string_parser(str: &String) -> Option<String> {
     if str.is_empty() {
         None
@@ -1,44 +1,427 @@
-use crate::qsl_adif_helper::adif_generate_header;
+use crate::qsl_adif_helper::{adif_generate_header, adif_parse};
+use crate::qsl_attachment::{Attachment, mime_type_of};
+use crate::qsl_cabrillo::{CabrilloHeader, cabrillo_qso_line};
+use crate::qsl_callsign::Callsign;
 use crate::qsl_context::QSLContext;
+use crate::qsl_country::CountryFile;
+use crate::qsl_history::Edit;
+use crate::qsl_province::{PROVINCES, Province, province_of};
+use crate::qsl_station::{EquipmentPreset, StationProfile};
 use crate::qsl_template::RecordTemplate;
-use crate::qsl_type::QSL;
+use crate::qsl_type::{OnAirStatistics, QSL, QSLFilter, RecordKind};
 use askama::Template;
-use chrono::{FixedOffset, Local, Utc};
+use chrono::{FixedOffset, NaiveDate, TimeDelta, Utc};
 use cursive::reexports::log;
-use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
 use std::path::Path;
 
 const TYPST_TEMPLATE: &str = include_str!("../templates/template.typ");
 pub(crate) struct QSLManager {
     pub context: QSLContext,
     pub(crate) split_page_size: i64,
+    /// Owner of the logbook, the heading of the HTML and Typst logs
     callsign: String,
+    /// Profile new records are made from
+    station: StationProfile,
+    /// Zone datetimes are entered and shown in, they are stored in UTC.
+    time_zone: FixedOffset,
+    /// Prefix table callsigns are resolved to DXCC entities with
+    country_file: Option<CountryFile>,
     pub page: usize,
+    /// Records shown in the table
+    filter: QSLFilter,
+    /// Tab of the table, eyeball meetings and on-air contacts are apart
+    tab: RecordKind,
+    /// Page each tab was left on
+    tab_pages: HashMap<RecordKind, usize>,
     max_page: usize,
     number_of_record: usize,
+    /// Edits of this session, the latest last
+    undo_stack: Vec<Edit>,
+    /// Edits undone, cleared by a new edit
+    redo_stack: Vec<Edit>,
 }
 
+/// Edits kept to undo, the oldest are dropped beyond it.
+const UNDO_LIMIT: usize = 100;
+
 impl QSLManager {
     pub fn new(context: QSLContext, split_page_size: i64) -> Result<Self, String> {
         let callsign = match context.get_callsign() {
             Ok(cs) => cs,
             Err(err) => return Err(format!("Could not read callsign: {err}")),
         };
-        let number_of_record = context.get_qsl_count()? as usize;
+        let station = context.get_station_profile(context.get_active_profile_id()?)?;
+        let time_zone = context.get_time_zone()?;
+        let country_file = match context.get_country_file()? {
+            Some(path) => match CountryFile::load(Path::new(&path)) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    eprintln!("Country file is not loaded: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+        match Self::purge_expired_trash(&context) {
+            Ok(0) => {}
+            Ok(purged) => println!("Purged {purged} record(s) kept in the trash too long."),
+            Err(e) => eprintln!("Trash is not purged: {e}"),
+        }
+        let number_of_record = context.get_qsl_count(&QSLFilter::All)? as usize;
         let max_page = (number_of_record / split_page_size as usize + 1) - 1;
 
         Ok(QSLManager {
             context,
             split_page_size,
             callsign,
+            station,
+            time_zone,
+            country_file,
             page: 0,
+            filter: QSLFilter::All,
+            tab: RecordKind::All,
+            tab_pages: HashMap::new(),
             max_page,
             number_of_record,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        })
+    }
+    pub fn station(&self) -> &StationProfile {
+        &self.station
+    }
+
+    /// Make another profile the one new records are made from.
+    pub fn set_active_station(&mut self, id: i32) -> Result<(), String> {
+        let station = self.context.get_station_profile(id)?;
+        self.context.set_active_profile_id(id)?;
+        self.station = station;
+        Ok(())
+    }
+
+    /// Update a profile, keeping the active one in sync.
+    pub fn update_station(&mut self, profile: StationProfile) -> Result<(), String> {
+        self.context.update_station_profile(&profile)?;
+        if profile.id == self.station.id {
+            self.station = profile;
+        }
+        Ok(())
+    }
+
+    pub fn delete_station(&mut self, id: i32) -> Result<(), String> {
+        if id == self.station.id {
+            return Err("The active station profile cannot be deleted.".to_string());
+        }
+        self.context.delete_station_profile(id)
+    }
+
+    /// Equipment of the last logged contact as an unsaved preset.
+    pub fn last_equipment(&self) -> Result<Option<EquipmentPreset>, String> {
+        Ok(self.context.get_last_qsl()?.map(|qsl| EquipmentPreset {
+            id: 0,
+            name: "Last QSO".to_string(),
+            qth: qsl.qth_me,
+            rig: qsl.rig_me,
+            ant: qsl.ant_me,
+            watt: qsl.watt_me,
+        }))
+    }
+
+    pub fn time_zone(&self) -> &FixedOffset {
+        &self.time_zone
+    }
+
+    /// Change the display zone. With `convert_existing`, records logged so far
+    /// are taken as local time in the new zone and converted to UTC, which the
+    /// logbook allows only once.
+    pub fn set_time_zone(
+        &mut self,
+        time_zone: FixedOffset,
+        convert_existing: bool,
+    ) -> Result<(), String> {
+        if convert_existing {
+            let count = self.context.convert_datetimes_to_utc(&time_zone)?;
+            log::debug!("QSLManager::set_time_zone: converted {count} record(s) to UTC");
+        }
+        self.context.set_time_zone(&time_zone)?;
+        self.time_zone = time_zone;
+        Ok(())
+    }
+
+    pub fn country_file(&self) -> Option<&CountryFile> {
+        self.country_file.as_ref()
+    }
+
+    /// Load a cty.dat or cty.csv and remember it, returning the number of entities.
+    pub fn load_country_file(&mut self, path: &str) -> Result<usize, String> {
+        let file = CountryFile::load(Path::new(path))?;
+        self.context.set_country_file(path)?;
+        let entities = file.entities();
+        self.country_file = Some(file);
+        Ok(entities)
+    }
+
+    /// Fill the DXCC entity and zones of a record from its callsign.
+    /// Without a country file the record is left as it is.
+    pub fn resolve_country(&self, qsl: &mut QSL) {
+        if let Some(file) = &self.country_file {
+            qsl.set_country(file.resolve(&qsl.call_number));
+        }
+    }
+
+    /// Resolve every record again, e.g. after loading a newer country file.
+    /// Returns the number of records with a known entity.
+    pub fn resolve_all_countries(&self) -> Result<usize, String> {
+        let file = match &self.country_file {
+            Some(file) => file,
+            None => return Err("No country file is loaded.".to_string()),
+        };
+        let mut resolved = 0;
+        for (id, callsign) in self.context.get_all_callsigns()? {
+            let country = file.resolve(&callsign);
+            if country.is_some() {
+                resolved += 1;
+            }
+            self.context.update_country(id, country.as_ref())?;
+        }
+        Ok(resolved)
+    }
+
+    /// Earlier contacts with the same base call in the same band and mode,
+    /// and the same contest when the record is part of one.
+    pub fn find_dupes(&self, qsl: &QSL) -> Result<Vec<QSL>, String> {
+        let band = |qsl: &QSL| qsl.freq.as_ref().and_then(|f| f.band().ok());
+        Ok(self
+            .context
+            .get_qsl_by_base_call(&Callsign::base_of(&qsl.call_number))?
+            .into_iter()
+            .filter(|other| {
+                other.id != qsl.id
+                    && other.mode == qsl.mode
+                    && band(other) == band(qsl)
+                    && other.contest_id == qsl.contest_id
+            })
+            .collect())
+    }
+
+    /// Every province with the number of on-air contacts that match `filter` made with it.
+    pub fn provinces_worked(
+        &self,
+        filter: &QSLFilter,
+    ) -> Result<Vec<(&'static Province, usize)>, String> {
+        let mut worked = PROVINCES.iter().map(|p| (p, 0)).collect::<Vec<_>>();
+        for (call_number, state, dxcc) in self.context.get_states(filter)? {
+            if let Some(province) = province_of(&call_number, Some(&state), dxcc)
+                && let Some((_, count)) = worked.iter_mut().find(|(p, _)| *p == province)
+            {
+                *count += 1;
+            }
+        }
+        Ok(worked)
+    }
+
+    /// Read a file into the logbook as an attachment of record `qsl_id`.
+    pub fn attach_file(&self, qsl_id: i32, path: &Path) -> Result<(), String> {
+        let data = fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let file_name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(format!("{} is not a file.", path.display())),
+        };
+        self.context.add_attachment(&Attachment {
+            id: 0,
+            qsl_id,
+            file_name,
+            mime_type: mime_type_of(path).to_string(),
+            data,
         })
     }
-    pub fn callsign(&self) -> &String {
-        &self.callsign
+
+    /// Copy every image of the records that match `filter` into `attachments`
+    /// of the output folder, and return the copies of each record relative to the folder.
+    fn output_images(
+        &self,
+        file_folder: &Path,
+        filter: &QSLFilter,
+    ) -> Result<HashMap<i32, Vec<String>>, String> {
+        let images = self.context.get_image_attachments(filter)?;
+        let mut result = HashMap::<i32, Vec<String>>::new();
+        if images.is_empty() {
+            return Ok(result);
+        }
+        let folder = file_folder.join("attachments");
+        fs::create_dir_all(&folder)
+            .map_err(|e| format!("Error on creating {}: {}", folder.display(), e))?;
+        for image in images {
+            let name = image.output_name();
+            fs::write(folder.join(&name), &image.data)
+                .map_err(|e| format!("Error on writing {name}: {}", e))?;
+            result
+                .entry(image.qsl_id)
+                .or_default()
+                .push(format!("attachments/{name}"));
+        }
+        Ok(result)
+    }
+
+    /// Add a record with its tags, it can be undone.
+    pub fn add_qsl(&mut self, qsl: QSL, tags: &[String]) -> Result<i32, String> {
+        let id = self.context.add_qsl(qsl)?;
+        self.context.set_tags(id, tags)?;
+        self.push_edit(Edit::Add(id));
+        Ok(id)
+    }
+
+    /// Update a record and its tags, it can be undone.
+    pub fn update_qsl(&mut self, qsl: QSL, tags: &[String]) -> Result<(), String> {
+        let id = qsl.id;
+        let before = self
+            .context
+            .snapshot(id)?
+            .ok_or(format!("QSL with ID {id} does not exist."))?;
+        let tags_before = self.context.get_tags(id)?;
+        self.context.update(qsl)?;
+        self.context.set_tags(id, tags)?;
+        let after = self
+            .context
+            .snapshot(id)?
+            .ok_or(format!("QSL with ID {id} does not exist."))?;
+        self.push_edit(Edit::Update {
+            id,
+            before,
+            after,
+            tags_before,
+            tags_after: tags.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Move a record to the trash, it can be undone.
+    pub fn delete_qsl(&mut self, id: i32) -> Result<(), String> {
+        self.context.delete(id)?;
+        self.push_edit(Edit::Delete(id));
+        Ok(())
+    }
+
+    fn push_edit(&mut self, edit: Edit) {
+        self.redo_stack.clear();
+        self.undo_stack.push(edit);
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// Undo the latest edit, none when there is nothing to undo.
+    /// An edit that fails to undo stays on the stack.
+    pub fn undo(&mut self) -> Result<Option<Edit>, String> {
+        let Some(edit) = self.undo_stack.pop() else {
+            return Ok(None);
+        };
+        let result = match &edit {
+            Edit::Add(id) => self.context.delete(*id),
+            Edit::Update {
+                id,
+                before,
+                tags_before,
+                ..
+            } => self.context.revert_to(*id, before, tags_before),
+            Edit::Delete(id) => self.context.restore(*id),
+        };
+        match result {
+            Ok(_) => {
+                self.redo_stack.push(edit.clone());
+                Ok(Some(edit))
+            }
+            Err(e) => {
+                self.undo_stack.push(edit);
+                Err(e)
+            }
+        }
+    }
+
+    /// Redo the latest undone edit, none when there is nothing to redo.
+    pub fn redo(&mut self) -> Result<Option<Edit>, String> {
+        let Some(edit) = self.redo_stack.pop() else {
+            return Ok(None);
+        };
+        let result = match &edit {
+            Edit::Add(id) => self.context.restore(*id),
+            Edit::Update {
+                id,
+                after,
+                tags_after,
+                ..
+            } => self.context.revert_to(*id, after, tags_after),
+            Edit::Delete(id) => self.context.delete(*id),
+        };
+        match result {
+            Ok(_) => {
+                self.undo_stack.push(edit.clone());
+                Ok(Some(edit))
+            }
+            Err(e) => {
+                self.redo_stack.push(edit);
+                Err(e)
+            }
+        }
+    }
+
+    /// Purge the records trashed longer than the retention period, if one is set.
+    fn purge_expired_trash(context: &QSLContext) -> Result<usize, String> {
+        match context.get_trash_retention()? {
+            Some(days) => context.purge_trash_before(Utc::now() - TimeDelta::days(days as i64)),
+            None => Ok(0),
+        }
+    }
+
+    /// Set how many days trashed records are kept and purge those past it now.
+    pub fn set_trash_retention(&self, days: Option<u32>) -> Result<usize, String> {
+        self.context.set_trash_retention(days)?;
+        Self::purge_expired_trash(&self.context)
+    }
+
+    pub fn on_air_statistics(&self, filter: &QSLFilter) -> Result<OnAirStatistics, String> {
+        let spans = self.context.get_time_spans(filter)?;
+        Ok(OnAirStatistics::from_spans(&spans))
+    }
+
+    pub fn filter(&self) -> &QSLFilter {
+        &self.filter
+    }
+
+    /// Show another set of records, starting from the first page.
+    pub fn set_filter(&mut self, filter: QSLFilter) {
+        self.filter = filter;
+        self.page = 0;
+        self.tab_pages.clear();
+    }
+
+    pub fn tab(&self) -> RecordKind {
+        self.tab
+    }
+
+    /// Switch the table to another tab, on the page it was left on.
+    pub fn set_tab(&mut self, tab: RecordKind) {
+        self.tab_pages.insert(self.tab, self.page);
+        self.tab = tab;
+        self.page = self.tab_pages.get(&tab).copied().unwrap_or_default();
+    }
+
+    /// Number of records a tab shows under the current filter.
+    pub fn tab_count(&self, tab: RecordKind) -> Result<usize, String> {
+        let count = match tab {
+            RecordKind::All => self.context.get_qsl_count(&self.filter)?,
+            RecordKind::Eyeball => self.context.get_eyeball_qsl_count(&self.filter)?,
+            RecordKind::OnAir => self.context.get_formal_qsl_count(&self.filter)?,
+        };
+        Ok(count as usize)
+    }
+
+    /// Number of pages `records` records take, counted like `max_page`.
+    pub fn page_count(&self, records: usize) -> usize {
+        records / self.split_page_size as usize + 1
     }
 
     pub fn max_page(&self) -> usize {
@@ -49,7 +432,7 @@ impl QSLManager {
         self.number_of_record
     }
     pub fn fetch_shown_qsl(&mut self) -> Vec<QSL> {
-        self.number_of_record = self.context.get_qsl_count().unwrap() as usize;
+        self.number_of_record = self.tab_count(self.tab).unwrap();
         log::debug!(
             "QSLManager::fetch_qsl: number of the record is {}",
             self.number_of_record
@@ -61,12 +444,21 @@ impl QSLManager {
             self.page = self.max_page
         }
 
-        self.context
-            .get_qsl_page(self.split_page_size, self.page as i64)
-            .unwrap()
+        let (page_size, page) = (self.split_page_size, self.page as i64);
+        match self.tab {
+            RecordKind::All => self.context.get_qsl_page(&self.filter, page_size, page),
+            RecordKind::Eyeball => self
+                .context
+                .get_eyeball_qsl_page(&self.filter, page_size, page),
+            RecordKind::OnAir => self
+                .context
+                .get_formal_qsl_page(&self.filter, page_size, page),
+        }
+        .unwrap()
     }
 
-    pub fn output_typst(&self, file: &mut File) -> Result<(), String> {
+    /// Write the on-air records that match `filter` as Typst data and the log template.
+    pub fn output_typst(&self, file: &mut File, filter: &QSLFilter) -> Result<(), String> {
         match file.write_all(format!("#let callsign = \"{}\"\n", self.callsign).as_bytes()) {
             Ok(_) => {}
             Err(e) => {
@@ -74,6 +466,13 @@ impl QSLManager {
             }
         }
 
+        match file.write_all(format!("#let time_zone = \"{}\"\n", self.time_zone).as_bytes()) {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("{}", e));
+            }
+        }
+
         match file.write_all("#let log_data = (".as_bytes()) {
             Ok(_) => {}
             Err(e) => {
@@ -81,18 +480,18 @@ impl QSLManager {
             }
         }
 
-        let record_count = self.context.get_formal_qsl_count()?;
+        let record_count = self.context.get_formal_qsl_count(filter)?;
         let total_pages = record_count / self.split_page_size + 1;
         println!("There are {} pages.", total_pages);
         for i in 0..total_pages {
             match self
                 .context
-                .get_formal_qsl_page(self.split_page_size, i as i64)
+                .get_formal_qsl_page(filter, self.split_page_size, i as i64)
             {
                 Ok(qsl_records) => {
                     println!("Page {} have {} records.", i, qsl_records.len());
                     for qsl in qsl_records {
-                        match file.write_all(qsl.fmt_typst().as_bytes()) {
+                        match file.write_all(qsl.fmt_typst(&self.time_zone).as_bytes()) {
                             Ok(_) => {}
                             Err(e) => {
                                 return Err(format!("{}", e));
@@ -123,8 +522,9 @@ impl QSLManager {
         Ok(())
     }
 
-    pub fn output_adif(&self, file: &mut File) -> Result<(), String> {
-        let datetime = Local::now();
+    /// Write the on-air records that match `filter` as ADIF.
+    pub fn output_adif(&self, file: &mut File, filter: &QSLFilter) -> Result<(), String> {
+        let datetime = Utc::now();
         println!("ADIF file will be created at {datetime}");
 
         match file.write_all(adif_generate_header(&datetime).as_ref()) {
@@ -134,18 +534,20 @@ impl QSLManager {
             }
         }
 
-        let record_count = self.context.get_formal_qsl_count()?;
+        let stations = self.context.get_station_profiles()?;
+        let record_count = self.context.get_formal_qsl_count(filter)?;
         let total_pages = record_count / self.split_page_size + 1;
         println!("There are {} pages.", total_pages);
         for i in 0..total_pages {
             match self
                 .context
-                .get_formal_qsl_page(self.split_page_size, i as i64)
+                .get_formal_qsl_page(filter, self.split_page_size, i as i64)
             {
                 Ok(qsl_records) => {
                     println!("Page {} have {} records.", i, qsl_records.len());
                     for qsl in qsl_records {
-                        match file.write_all(qsl.fmt_adif().as_bytes()) {
+                        let station = stations.iter().find(|s| Some(s.id) == qsl.station_profile);
+                        match file.write_all(qsl.fmt_adif(station).as_bytes()) {
                             Ok(_) => {}
                             Err(e) => {
                                 return Err(format!("{}", e));
@@ -162,33 +564,180 @@ impl QSLManager {
         Ok(())
     }
 
-    pub fn output_html(&self, file_folder: &Path) -> Result<(), String> {
+    /// Cabrillo header filled from the active station profile.
+    pub fn cabrillo_header(&self, contest_id: &str) -> CabrilloHeader {
+        CabrilloHeader::new(
+            contest_id,
+            &self.station.callsign,
+            self.station.operator.as_deref(),
+        )
+    }
+
+    /// Write the contacts of a contest that match `filter`, between two UTC
+    /// dates inclusive when given.
+    pub fn output_cabrillo(
+        &self,
+        file: &mut File,
+        header: &CabrilloHeader,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        filter: &QSLFilter,
+    ) -> Result<(), String> {
+        let qsl_records = self.context.get_contest_qsl(
+            &header.contest,
+            from.map(|d| d.and_hms_opt(0, 0, 0).unwrap()),
+            to.map(|d| d.and_hms_opt(0, 0, 0).unwrap() + TimeDelta::days(1)),
+            filter,
+        )?;
+        println!("There are {} records.", qsl_records.len());
+
+        match file.write_all(header.fmt_cabrillo().as_bytes()) {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("{}", e));
+            }
+        }
+
+        let stations = self.context.get_station_profiles()?;
+        for qsl in qsl_records {
+            let my_call = stations
+                .iter()
+                .find(|s| Some(s.id) == qsl.station_profile)
+                .map_or(header.callsign.as_str(), |s| s.callsign.as_str());
+            match cabrillo_qso_line(&qsl, my_call) {
+                Ok(line) => match file.write_all(line.as_bytes()) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        return Err(format!("{}", e));
+                    }
+                },
+                Err(e) => eprintln!("Record {} is not written: {e}", qsl.id),
+            }
+        }
+
+        match file.write_all("END-OF-LOG:\n".as_bytes()) {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("{}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn import_adif(&self, file: &mut File) -> Result<(), String> {
+        let mut content = Vec::new();
+        if let Err(e) = file.read_to_end(&mut content) {
+            return Err(format!("{}", e));
+        }
+
+        let records = adif_parse(&content);
+        println!("There are {} records.", records.len());
+        let mut stations = self.context.get_station_profiles()?;
+        let mut imported = 0;
+        for (i, record) in records.iter().enumerate() {
+            let result = record
+                .as_ref()
+                .map_err(|e| e.to_string())
+                .and_then(|fields| {
+                    let mut qsl = QSL::from_adif(fields)?;
+                    if qsl.country_info().is_none() && qsl.dxcc.is_none() {
+                        self.resolve_country(&mut qsl);
+                    }
+                    if qsl.state.is_none() {
+                        qsl.state =
+                            Province::from_callsign(&qsl.call_number).map(|p| p.code.to_string());
+                    }
+                    qsl.station_profile = Some(self.station_for_import(fields, &mut stations)?);
+                    self.context.add_qsl(qsl)
+                });
+            match result {
+                Ok(_) => imported += 1,
+                Err(e) => eprintln!("Record {} is not imported: {e}", i + 1),
+            }
+        }
+        println!("Imported {} of {} records.", imported, records.len());
+
+        Ok(())
+    }
+
+    /// Profile of an imported record from its STATION_CALLSIGN and OPERATOR.
+    /// Unknown stations get a new profile, records without them go to the active one.
+    fn station_for_import(
+        &self,
+        fields: &HashMap<String, String>,
+        stations: &mut Vec<StationProfile>,
+    ) -> Result<i32, String> {
+        let get = |key: &str| {
+            fields
+                .get(key)
+                .map(|v| v.trim().to_uppercase())
+                .filter(|v| !v.is_empty())
+        };
+        let operator = get("OPERATOR");
+        let callsign = match get("STATION_CALLSIGN").or(operator.clone()) {
+            Some(callsign) => callsign,
+            None => return Ok(self.station.id),
+        };
+
+        if let Some(station) = stations
+            .iter()
+            .find(|s| s.callsign == callsign && (operator.is_none() || s.operator == operator))
+        {
+            return Ok(station.id);
+        }
+        let mut station = StationProfile {
+            id: 0,
+            callsign,
+            operator,
+            qth: None,
+            grid: None,
+            rig: None,
+            ant: None,
+            watt: None,
+        };
+        station.id = self.context.add_station_profile(&station)?;
+        println!("Station profile {station} is created.");
+        stations.push(station.clone());
+        Ok(station.id)
+    }
+
+    /// Write `index.html` of the records that match `filter` into `file_folder`.
+    pub fn output_html(&self, file_folder: &Path, filter: &QSLFilter) -> Result<(), String> {
         let mut record_eyeball = Vec::<QSL>::new();
         let mut record_normal = Vec::<QSL>::new();
 
         // First, Eyeball page
-        let record_count = self.context.get_eyeball_qsl_count()?;
+        let record_count = self.context.get_eyeball_qsl_count(filter)?;
         let total_pages = record_count / self.split_page_size + 1;
 
         for i in 0..total_pages {
-            let mut qsl_records = self.context.get_eyeball_qsl_page(self.split_page_size, i)?;
+            let mut qsl_records =
+                self.context
+                    .get_eyeball_qsl_page(filter, self.split_page_size, i)?;
             record_eyeball.append(&mut qsl_records);
         }
 
-        let record_count = self.context.get_formal_qsl_count()?;
+        let record_count = self.context.get_formal_qsl_count(filter)?;
         let total_pages = record_count / self.split_page_size + 1;
 
         for i in 0..total_pages {
-            let mut qsl_records = self.context.get_formal_qsl_page(self.split_page_size, i)?;
+            let mut qsl_records =
+                self.context
+                    .get_formal_qsl_page(filter, self.split_page_size, i)?;
             record_normal.append(&mut qsl_records);
         }
 
         let template = RecordTemplate {
             callsign: &self.callsign,
             datetime: &Utc::now()
-                .with_timezone(&FixedOffset::east_opt(8 * 3600).unwrap())
+                .with_timezone(&self.time_zone)
                 .format("%Y-%m-%d %H:%M:%S")
                 .to_string(),
+            time_zone: &self.time_zone,
+            on_air: &self.on_air_statistics(filter)?,
+            provinces: &self.provinces_worked(filter)?,
+            images: &self.output_images(file_folder, filter)?,
             records_formal: &record_normal,
             records_eyeball: &record_eyeball,
         };
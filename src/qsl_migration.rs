@@ -0,0 +1,928 @@
+use cursive::reexports::log;
+use rusqlite::{Connection, Error, Transaction, params};
+
+/// One schema upgrade step, run inside the migration transaction.
+type Migration = fn(&Transaction) -> Result<(), Error>;
+
+/// Ordered upgrade steps. Entry `i` moves the database from version `i + 1`
+/// to version `i + 2`. Version 1 is the original layout created by
+/// `NEW_DATABASE_QUERY`, which logbooks made before versioning also have.
+const MIGRATIONS: &[Migration] = &[
+    add_satellite_columns,
+    add_grid_columns,
+    add_location_columns,
+    store_mode_as_text,
+    store_frequency_as_numbers,
+    add_time_zone_setting,
+    add_end_time_column,
+    add_card_columns,
+    add_station_profiles,
+    add_equipment_presets,
+    add_contest_columns,
+    add_country_columns,
+    add_base_call_column,
+    add_state_column,
+    add_contact_columns,
+    add_attachment_table,
+    add_tag_tables,
+    add_history_table,
+    add_trash,
+];
+
+/// Schema version this binary reads and writes.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64 + 1;
+
+/// Read the schema version stored in `PRAGMA user_version`.
+/// Logbooks created before versioning report 0.
+fn stored_version(connection: &Connection) -> Result<i64, String> {
+    match connection.pragma_query_value(None, "user_version", |row| row.get::<_, i64>(0)) {
+        Ok(version) => Ok(version),
+        Err(e) => Err(format!("Failed to read schema version: {}", e)),
+    }
+}
+
+/// Bring the database up to `SCHEMA_VERSION`, applying every pending step in
+/// a single transaction. Refuses to touch databases newer than this binary.
+pub fn migrate(connection: &mut Connection) -> Result<(), String> {
+    let stored = stored_version(connection)?;
+    if stored == SCHEMA_VERSION {
+        log::debug!("Migration: schema is up to date at version {stored}");
+        return Ok(());
+    }
+    // Unversioned logbooks have the version 1 layout.
+    let current = stored.max(1);
+    if current > SCHEMA_VERSION {
+        return Err(format!(
+            "Database schema version {} is newer than supported version {}, please upgrade qsl_recorder.",
+            current, SCHEMA_VERSION
+        ));
+    }
+
+    let transaction = connection
+        .transaction()
+        .map_err(|e| format!("Failed to start migration transaction: {}", e))?;
+
+    for (index, step) in MIGRATIONS.iter().enumerate().skip(current as usize - 1) {
+        let target = index as i64 + 2;
+        println!("Upgrading database schema to version {}.", target);
+        if let Err(e) = step(&transaction) {
+            return Err(format!(
+                "Failed to upgrade database schema to version {}: {}",
+                target, e
+            ));
+        }
+    }
+
+    if let Err(e) = transaction.pragma_update(None, "user_version", SCHEMA_VERSION) {
+        return Err(format!("Failed to write schema version: {}", e));
+    }
+
+    match transaction.commit() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to commit migration: {}", e)),
+    }
+}
+
+/// Version 2: satellite name, mode and separate uplink/downlink frequencies.
+fn add_satellite_columns(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN sat_name TEXT;
+ALTER TABLE qsl ADD COLUMN sat_mode TEXT;
+ALTER TABLE qsl ADD COLUMN freq_uplink REAL;
+ALTER TABLE qsl ADD COLUMN freq_downlink REAL;
+"#,
+    )
+}
+
+/// Version 3: Maidenhead locators of both stations.
+fn add_grid_columns(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN grid_me TEXT;
+ALTER TABLE qsl ADD COLUMN grid_counterpart TEXT;
+"#,
+    )
+}
+
+/// Version 4: coordinates of both stations.
+fn add_location_columns(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN lat_me REAL;
+ALTER TABLE qsl ADD COLUMN lon_me REAL;
+ALTER TABLE qsl ADD COLUMN lat_counterpart REAL;
+ALTER TABLE qsl ADD COLUMN lon_counterpart REAL;
+"#,
+    )
+}
+
+/// Version 5: ADIF MODE and SUBMODE as text instead of the `call_type` integer.
+/// The old FTB value was FT8, OTHER is kept until the record is edited.
+fn store_mode_as_text(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN mode TEXT NOT NULL DEFAULT 'OTHER';
+ALTER TABLE qsl ADD COLUMN submode TEXT;
+UPDATE qsl SET mode = CASE call_type
+    WHEN 0 THEN 'EYEBALL'
+    WHEN 1 THEN 'FM'
+    WHEN 2 THEN 'SSB'
+    WHEN 3 THEN 'CW'
+    WHEN 4 THEN 'FT8'
+    ELSE 'OTHER'
+END;
+DROP VIEW eyeball_qsl;
+DROP VIEW formal_qsl;
+ALTER TABLE qsl DROP COLUMN call_type;
+CREATE VIEW eyeball_qsl AS SELECT * FROM qsl WHERE mode = 'EYEBALL';
+CREATE VIEW formal_qsl AS SELECT * FROM qsl WHERE mode != 'EYEBALL';
+"#,
+    )
+}
+
+/// Version 6: the free text `freq` and the satellite uplink/downlink become
+/// `freq_tx`, `freq_rx` and `tone`. Text that cannot be read is kept in the note.
+fn store_frequency_as_numbers(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN freq_tx REAL;
+ALTER TABLE qsl ADD COLUMN freq_rx REAL;
+ALTER TABLE qsl ADD COLUMN tone TEXT;
+"#,
+    )?;
+
+    let mut stmt =
+        transaction.prepare("SELECT id, freq, freq_uplink, freq_downlink, note FROM qsl")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<f64>>(2)?,
+                row.get::<_, Option<f64>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (id, freq, uplink, downlink, note) in rows {
+        let mut note = note;
+        let mut freq = match freq.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            None => None,
+            Some(text) => match v6::parse_frequency(text) {
+                Ok(freq) => Some(freq),
+                Err(e) => {
+                    log::warn!("Migration: record {id} frequency {text} moved to note: {e}");
+                    let kept = format!("Frequency: {text}");
+                    note = Some(match note {
+                        Some(note) => format!("{note} {kept}"),
+                        None => kept,
+                    });
+                    None
+                }
+            },
+        };
+        // Satellite records knew their uplink and downlink better than the text.
+        if let Some(tx) = uplink {
+            freq = Some((tx, downlink, None));
+        }
+
+        transaction.execute(
+            "UPDATE qsl SET freq_tx = ?1, freq_rx = ?2, tone = ?3, note = ?4 WHERE id = ?5",
+            params![
+                freq.as_ref().map(|(tx, _, _)| *tx),
+                freq.as_ref().and_then(|(_, rx, _)| *rx),
+                freq.as_ref().and_then(|(_, _, tone)| tone.clone()),
+                note,
+                id
+            ],
+        )?;
+        log::debug!(
+            "Migration: record {id} frequency is {}",
+            freq.as_ref()
+                .map_or("".to_string(), |(tx, _, _)| tx.to_string())
+        );
+    }
+    drop(stmt);
+
+    transaction.execute_batch(
+        r#"
+DROP VIEW eyeball_qsl;
+DROP VIEW formal_qsl;
+ALTER TABLE qsl DROP COLUMN freq;
+ALTER TABLE qsl DROP COLUMN freq_uplink;
+ALTER TABLE qsl DROP COLUMN freq_downlink;
+CREATE VIEW eyeball_qsl AS SELECT * FROM qsl WHERE mode = 'EYEBALL';
+CREATE VIEW formal_qsl AS SELECT * FROM qsl WHERE mode != 'EYEBALL';
+"#,
+    )
+}
+
+/// Version 7: time zone datetimes are entered and shown in. Datetimes in `qsl`
+/// are UTC from now on, existing logbooks keep +00:00 so nothing moves until
+/// the owner picks a zone. `datetimes_converted` records whether the records
+/// logged in local time were converted to UTC, so that runs at most once.
+/// Logbooks without records have nothing to convert.
+fn add_time_zone_setting(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE setting ADD COLUMN time_zone TEXT NOT NULL DEFAULT '+00:00';
+ALTER TABLE setting ADD COLUMN datetimes_converted INTEGER NOT NULL DEFAULT 0;
+UPDATE setting SET datetimes_converted = 1 WHERE NOT EXISTS (SELECT 1 FROM qsl);
+"#,
+    )
+}
+
+/// Version 8: optional end of the contact in UTC, ADIF QSO_DATE_OFF / TIME_OFF.
+fn add_end_time_column(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch("ALTER TABLE qsl ADD COLUMN datetime_off TEXT;")
+}
+
+/// Version 9: paper, eQSL and LoTW sent and received status with their dates,
+/// as ADIF letters and YYYY-MM-DD, and the route of paper cards.
+fn add_card_columns(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN qsl_sent TEXT;
+ALTER TABLE qsl ADD COLUMN qsl_sent_date TEXT;
+ALTER TABLE qsl ADD COLUMN qsl_rcvd TEXT;
+ALTER TABLE qsl ADD COLUMN qsl_rcvd_date TEXT;
+ALTER TABLE qsl ADD COLUMN eqsl_sent TEXT;
+ALTER TABLE qsl ADD COLUMN eqsl_sent_date TEXT;
+ALTER TABLE qsl ADD COLUMN eqsl_rcvd TEXT;
+ALTER TABLE qsl ADD COLUMN eqsl_rcvd_date TEXT;
+ALTER TABLE qsl ADD COLUMN lotw_sent TEXT;
+ALTER TABLE qsl ADD COLUMN lotw_sent_date TEXT;
+ALTER TABLE qsl ADD COLUMN lotw_rcvd TEXT;
+ALTER TABLE qsl ADD COLUMN lotw_rcvd_date TEXT;
+ALTER TABLE qsl ADD COLUMN qsl_sent_via TEXT;
+ALTER TABLE qsl ADD COLUMN qsl_rcvd_via TEXT;
+ALTER TABLE qsl ADD COLUMN qsl_via TEXT;
+"#,
+    )
+}
+
+/// Version 10: station profiles. The logbook callsign becomes the first profile,
+/// which is active and owns every existing record.
+fn add_station_profiles(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+CREATE TABLE station_profile (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    callsign TEXT NOT NULL,
+    operator TEXT,
+    qth TEXT,
+    grid TEXT,
+    rig TEXT,
+    ant TEXT,
+    watt REAL
+);
+INSERT INTO station_profile (callsign) SELECT call_number FROM setting;
+ALTER TABLE setting ADD COLUMN active_profile INTEGER REFERENCES station_profile(id);
+UPDATE setting SET active_profile = (SELECT MIN(id) FROM station_profile);
+ALTER TABLE qsl ADD COLUMN station_profile INTEGER REFERENCES station_profile(id);
+UPDATE qsl SET station_profile = (SELECT active_profile FROM setting);
+"#,
+    )
+}
+
+/// Version 11: named equipment presets, and whether new records start with the
+/// equipment of the last logged contact.
+fn add_equipment_presets(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+CREATE TABLE equipment_preset (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    qth TEXT,
+    rig TEXT,
+    ant TEXT,
+    watt REAL
+);
+ALTER TABLE setting ADD COLUMN prefill_from_last INTEGER NOT NULL DEFAULT 0;
+"#,
+    )
+}
+
+/// Version 12: contest name and the exchanged serial numbers or strings.
+fn add_contest_columns(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN contest_id TEXT;
+ALTER TABLE qsl ADD COLUMN stx INTEGER;
+ALTER TABLE qsl ADD COLUMN srx INTEGER;
+ALTER TABLE qsl ADD COLUMN stx_string TEXT;
+ALTER TABLE qsl ADD COLUMN srx_string TEXT;
+"#,
+    )
+}
+
+/// Version 13: DXCC entity and zones of the counterpart, and the country file
+/// they are resolved from.
+fn add_country_columns(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN dxcc INTEGER;
+ALTER TABLE qsl ADD COLUMN country TEXT;
+ALTER TABLE qsl ADD COLUMN cont TEXT;
+ALTER TABLE qsl ADD COLUMN cqz INTEGER;
+ALTER TABLE qsl ADD COLUMN ituz INTEGER;
+ALTER TABLE setting ADD COLUMN country_file TEXT;
+"#,
+    )
+}
+
+/// Version 14: callsigns in upper case with the prefix before the base call,
+/// and the base call on its own for search, dupes and statistics.
+fn add_base_call_column(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch("ALTER TABLE qsl ADD COLUMN base_call TEXT;")?;
+
+    let mut stmt = transaction.prepare("SELECT id, call_number FROM qsl")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (id, call_number) in rows {
+        let (call_number, base_call) = match v14::parse_callsign(&call_number) {
+            Ok(callsign) => (callsign.to_string(), callsign.base),
+            Err(e) => {
+                log::warn!("Migration: record {id} callsign kept as it is: {e}");
+                let upper = call_number.trim().to_uppercase();
+                (upper.clone(), upper)
+            }
+        };
+        transaction.execute(
+            "UPDATE qsl SET call_number = ?1, base_call = ?2 WHERE id = ?3",
+            params![call_number, base_call, id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Version 15: province of Chinese counterparts, derived from their callsigns.
+fn add_state_column(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch("ALTER TABLE qsl ADD COLUMN state TEXT;")?;
+
+    let mut stmt = transaction.prepare("SELECT id, call_number FROM qsl")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (id, call_number) in rows {
+        if let Some(code) = v15::province_of_callsign(&call_number) {
+            transaction.execute("UPDATE qsl SET state = ?1 WHERE id = ?2", params![code, id])?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 16: name, email and address of the counterpart, and the contact
+/// details remembered for each base call, seeded from the QSL via of old records.
+fn add_contact_columns(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN name_counterpart TEXT;
+ALTER TABLE qsl ADD COLUMN email_counterpart TEXT;
+ALTER TABLE qsl ADD COLUMN address_counterpart TEXT;
+CREATE TABLE contact (
+    base_call TEXT PRIMARY KEY,
+    name TEXT,
+    email TEXT,
+    address TEXT,
+    qsl_via TEXT
+);
+INSERT INTO contact (base_call, qsl_via)
+SELECT base_call, qsl_via FROM qsl AS q
+WHERE qsl_via IS NOT NULL
+  AND datetime = (SELECT MAX(datetime) FROM qsl WHERE base_call = q.base_call AND qsl_via IS NOT NULL)
+GROUP BY base_call;
+"#,
+    )
+}
+
+/// Version 17: files attached to records, kept in the logbook itself.
+fn add_attachment_table(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+CREATE TABLE attachment (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    qsl_id INTEGER NOT NULL REFERENCES qsl(id),
+    file_name TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    data BLOB NOT NULL
+);
+CREATE INDEX attachment_qsl_id ON attachment(qsl_id);
+"#,
+    )
+}
+
+/// Version 18: free-form tags, each record may have many and a tag many records.
+fn add_tag_tables(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+CREATE TABLE tag (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE qsl_tag (
+    qsl_id INTEGER NOT NULL REFERENCES qsl(id),
+    tag_id INTEGER NOT NULL REFERENCES tag(id),
+    PRIMARY KEY (qsl_id, tag_id)
+);
+"#,
+    )
+}
+
+/// Version 19: every insert, update and delete of a record, with the record
+/// before and after as JSON and the station profile that was active.
+fn add_history_table(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+CREATE TABLE qsl_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    qsl_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    changed_at TEXT NOT NULL,
+    station_profile INTEGER REFERENCES station_profile(id),
+    old_record TEXT,
+    new_record TEXT
+);
+CREATE INDEX qsl_history_qsl_id ON qsl_history(qsl_id);
+"#,
+    )
+}
+
+/// Version 20: deleting moves a record to the trash by stamping `deleted_at`.
+/// `live_qsl` leaves trashed records out, and the eyeball and on-air views
+/// are built on it. Trashed records are purged after `trash_retention_days`
+/// when it is set.
+fn add_trash(transaction: &Transaction) -> Result<(), Error> {
+    transaction.execute_batch(
+        r#"
+ALTER TABLE qsl ADD COLUMN deleted_at TEXT;
+ALTER TABLE setting ADD COLUMN trash_retention_days INTEGER;
+DROP VIEW eyeball_qsl;
+DROP VIEW formal_qsl;
+CREATE VIEW live_qsl AS SELECT * FROM qsl WHERE deleted_at IS NULL;
+CREATE VIEW eyeball_qsl AS SELECT * FROM live_qsl WHERE mode = 'EYEBALL';
+CREATE VIEW formal_qsl AS SELECT * FROM live_qsl WHERE mode != 'EYEBALL';
+"#,
+    )
+}
+
+// Parsers as they were when the migrations above were written. A migration
+// must give the same result on every logbook it ever runs on, so these are
+// frozen copies instead of calls to the live parsers, which keep changing.
+
+/// Frequency text of version 6, `Frequency::parse` and `Tone::parse` of that time.
+mod v6 {
+    /// Read "145.500", "7074 kHz", "438.500/-7.6/T88.5" or "436.795/145.850"
+    /// into where I transmit, where I listen if different, and the tone text.
+    pub(super) fn parse_frequency(
+        text: &str,
+    ) -> Result<(f64, Option<f64>, Option<String>), String> {
+        let parts = text.split('/').map(|p| p.trim()).collect::<Vec<_>>();
+        let listen = parse_mhz(parts[0])?;
+
+        let mut transmit = None;
+        let mut tone = None;
+        for part in &parts[1..] {
+            let upper = part.to_uppercase();
+            let is_tone = upper.starts_with(['T', 'C', 'D'])
+                || (upper.ends_with("HZ")
+                    && !["KHZ", "MHZ", "GHZ"].iter().any(|u| upper.ends_with(u)));
+            if is_tone {
+                if tone.is_some() {
+                    return Err(format!("Frequency {text} has more than one tone."));
+                }
+                tone = Some(parse_tone(part)?);
+                continue;
+            }
+            if transmit.is_some() {
+                return Err(format!(
+                    "Frequency {text} has more than one shift, write it like 438.500/-7.6/T88.5."
+                ));
+            }
+            transmit = Some(if part.starts_with(['+', '-']) {
+                ((listen + parse_signed_mhz(part)?) * 1e6).round() / 1e6
+            } else {
+                parse_mhz(part)?
+            });
+        }
+
+        Ok(match transmit {
+            Some(tx) if (tx - listen).abs() > 1e-9 => (tx, Some(listen), tone),
+            _ => (listen, None, tone),
+        })
+    }
+
+    /// Read "T88.5", "C88.5", "88.5Hz" or "D023" into "T88.5" or "D023".
+    fn parse_tone(text: &str) -> Result<String, String> {
+        let text = text.trim();
+        let upper = text.to_uppercase();
+        if let Some(code) = upper.strip_prefix('D') {
+            let code = code.trim();
+            if !(3..=4).contains(&code.len()) || !code.chars().all(|c| ('0'..='7').contains(&c)) {
+                return Err(format!(
+                    "DCS code {text} should be 3 or 4 octal digits, e.g. D023."
+                ));
+            }
+            return Ok(format!("D{code}"));
+        }
+        let value = match upper.strip_prefix(['T', 'C']) {
+            Some(value) => value,
+            None => upper
+                .strip_suffix("HZ")
+                .ok_or(format!("Tone {text} should be written like T88.5 or D023."))?,
+        };
+        match value.trim().parse::<f64>() {
+            Ok(hz) if (30.0..=300.0).contains(&hz) => Ok(format!("T{hz}")),
+            Ok(_) => Err(format!(
+                "CTCSS tone {text} should be between 30 and 300 Hz."
+            )),
+            Err(e) => Err(format!("Cannot read tone {text}: {e}")),
+        }
+    }
+
+    fn parse_mhz(text: &str) -> Result<f64, String> {
+        let mhz = parse_signed_mhz(text)?;
+        if mhz <= 0.0 {
+            return Err(format!("Frequency {text} should be positive."));
+        }
+        Ok(mhz)
+    }
+
+    fn parse_signed_mhz(text: &str) -> Result<f64, String> {
+        let upper = text.trim().to_uppercase();
+        let (number, scale) = if let Some(n) = upper.strip_suffix("GHZ") {
+            (n, 1000.0)
+        } else if let Some(n) = upper.strip_suffix("MHZ") {
+            (n, 1.0)
+        } else if let Some(n) = upper.strip_suffix("KHZ") {
+            (n, 0.001)
+        } else if let Some(n) = upper.strip_suffix("HZ") {
+            (n, 0.000_001)
+        } else if let Some(n) = upper.strip_suffix('G') {
+            (n, 1000.0)
+        } else if let Some(n) = upper.strip_suffix('M') {
+            (n, 1.0)
+        } else if let Some(n) = upper.strip_suffix('K') {
+            (n, 0.001)
+        } else {
+            (upper.as_str(), 1.0)
+        };
+        match number.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value * scale),
+            Ok(_) => Err(format!("Frequency {text} is not a number.")),
+            Err(e) => Err(format!("Cannot read frequency {text}: {e}")),
+        }
+    }
+}
+
+/// Callsigns of version 14, `Callsign::parse` of that time.
+mod v14 {
+    use std::fmt::Display;
+
+    const MODIFIERS: [&str; 5] = ["P", "M", "MM", "AM", "QRP"];
+
+    pub(super) struct Callsign {
+        pub(super) prefix: Option<String>,
+        pub(super) base: String,
+        pub(super) area: Option<char>,
+        pub(super) modifiers: Vec<String>,
+    }
+
+    impl Display for Callsign {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            if let Some(prefix) = &self.prefix {
+                write!(f, "{prefix}/")?;
+            }
+            write!(f, "{}", self.base)?;
+            if let Some(area) = self.area {
+                write!(f, "/{area}")?;
+            }
+            for modifier in &self.modifiers {
+                write!(f, "/{modifier}")?;
+            }
+            Ok(())
+        }
+    }
+
+    pub(super) fn parse_callsign(text: &str) -> Result<Callsign, String> {
+        let upper = text.trim().to_uppercase();
+        let mut calls = Vec::new();
+        let mut area = None;
+        let mut modifiers = Vec::new();
+        for part in upper.split('/') {
+            if part.is_empty() {
+                return Err(format!("{upper} has an empty part."));
+            }
+            if MODIFIERS.contains(&part) {
+                modifiers.push(part.to_string());
+            } else if part.len() == 1 && part.chars().all(|c| c.is_ascii_digit()) {
+                if area.is_some() {
+                    return Err(format!("{upper} has more than one call area."));
+                }
+                area = part.chars().next();
+            } else {
+                calls.push(part);
+            }
+        }
+
+        let (prefix, base) = match calls[..] {
+            [base] => (None, base),
+            [first, second] => {
+                let first_is_prefix = match (is_prefix(first), is_prefix(second)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => first.len() <= second.len(),
+                };
+                if first_is_prefix {
+                    (Some(first), second)
+                } else {
+                    (Some(second), first)
+                }
+            }
+            [] => return Err(format!("{upper} has no callsign.")),
+            _ => return Err(format!("{upper} has more than two callsigns.")),
+        };
+        if !is_base_call(base) {
+            return Err(format!("{base} is not a valid callsign."));
+        }
+        if let Some(prefix) = prefix
+            && !is_prefix(prefix)
+            && !is_base_call(prefix)
+        {
+            return Err(format!("{prefix} is not a valid prefix."));
+        }
+
+        Ok(Callsign {
+            prefix: prefix.map(|p| p.to_string()),
+            base: base.to_string(),
+            area,
+            modifiers,
+        })
+    }
+
+    fn prefix_forms(text: &str) -> impl Iterator<Item = usize> + '_ {
+        let bytes = text.as_bytes();
+        let one = bytes.first().is_some_and(u8::is_ascii_uppercase);
+        let two = bytes.len() >= 2
+            && bytes[..2].iter().all(u8::is_ascii_alphanumeric)
+            && bytes[..2].iter().any(u8::is_ascii_uppercase);
+        [(one, 1), (two, 2)]
+            .into_iter()
+            .filter(|(valid, _)| *valid)
+            .map(|(_, len)| len)
+    }
+
+    fn is_base_call(call: &str) -> bool {
+        let bytes = call.as_bytes();
+        prefix_forms(call).any(|len| {
+            let rest = &bytes[len..];
+            rest.len() >= 2
+                && rest.len() <= 5
+                && rest[0].is_ascii_digit()
+                && rest[1..].iter().all(u8::is_ascii_alphanumeric)
+                && rest.last().is_some_and(u8::is_ascii_uppercase)
+        })
+    }
+
+    fn is_prefix(text: &str) -> bool {
+        let bytes = text.as_bytes();
+        prefix_forms(text).any(|len| match &bytes[len..] {
+            [] => true,
+            [digit] => digit.is_ascii_digit(),
+            _ => false,
+        })
+    }
+}
+
+/// Provinces of version 15, `Province::from_callsign` of that time.
+mod v15 {
+    use super::v14;
+
+    const DISTRICTS: [(char, char, char, &str); 31] = [
+        ('1', 'A', 'Z', "BJ"),
+        ('2', 'A', 'H', "HL"),
+        ('2', 'I', 'P', "JL"),
+        ('2', 'Q', 'X', "LN"),
+        ('3', 'A', 'F', "TJ"),
+        ('3', 'G', 'L', "NM"),
+        ('3', 'M', 'R', "HE"),
+        ('3', 'S', 'X', "SX"),
+        ('4', 'A', 'H', "SH"),
+        ('4', 'I', 'P', "SD"),
+        ('4', 'Q', 'X', "JS"),
+        ('5', 'A', 'H', "ZJ"),
+        ('5', 'I', 'P', "JX"),
+        ('5', 'Q', 'X', "FJ"),
+        ('6', 'A', 'H', "AH"),
+        ('6', 'I', 'P', "HA"),
+        ('6', 'Q', 'X', "HB"),
+        ('7', 'A', 'H', "HN"),
+        ('7', 'I', 'P', "GD"),
+        ('7', 'Q', 'X', "GX"),
+        ('7', 'Y', 'Z', "HI"),
+        ('8', 'A', 'F', "SC"),
+        ('8', 'G', 'L', "CQ"),
+        ('8', 'M', 'R', "GZ"),
+        ('8', 'S', 'X', "YN"),
+        ('9', 'A', 'F', "SN"),
+        ('9', 'G', 'L', "GS"),
+        ('9', 'M', 'R', "NX"),
+        ('9', 'S', 'X', "QH"),
+        ('0', 'A', 'F', "XJ"),
+        ('0', 'G', 'L', "XZ"),
+    ];
+
+    const CHINA_PREFIX_LETTERS: &str = "ABCDEFGHIJKLRTYZ";
+
+    /// ADIF STATE of a Chinese callsign operated from home, e.g. GD of BG7IJK.
+    pub(super) fn province_of_callsign(text: &str) -> Option<&'static str> {
+        let callsign = v14::parse_callsign(text).ok()?;
+        if callsign.prefix.is_some()
+            || callsign.area.is_some()
+            || callsign.modifiers.iter().any(|m| m == "MM" || m == "AM")
+            || !is_china(&callsign.base)
+        {
+            return None;
+        }
+        let base = callsign.base.as_bytes();
+        let digit = base.iter().position(u8::is_ascii_digit)?;
+        let area = base[digit] as char;
+        let letter = *base.get(digit + 1)? as char;
+        let (_, _, _, code) = DISTRICTS
+            .iter()
+            .find(|(a, first, last, _)| *a == area && (*first..=*last).contains(&letter))?;
+        Some(code)
+    }
+
+    fn is_china(call: &str) -> bool {
+        let bytes = call.as_bytes();
+        let letters = bytes.iter().take_while(|b| b.is_ascii_uppercase()).count();
+        match &bytes[..letters] {
+            [b'B'] => true,
+            [b'B', letter] => CHINA_PREFIX_LETTERS.as_bytes().contains(letter),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qsl_context::NEW_DATABASE_QUERY;
+
+    /// A logbook with the version 1 layout and the records given as
+    /// (call_number, call_type, freq, note).
+    fn version_1(records: &[(&str, i32, &str, Option<&str>)]) -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(NEW_DATABASE_QUERY).unwrap();
+        connection
+            .execute("INSERT INTO setting (call_number) VALUES ('BY9CLB')", [])
+            .unwrap();
+        for (call_number, call_type, freq, note) in records {
+            connection
+                .execute(
+                    "INSERT INTO qsl (call_number, call_type, freq, datetime, note)
+                     VALUES (?1, ?2, ?3, '2024-05-01 12:00:00', ?4)",
+                    params![call_number, call_type, freq, note],
+                )
+                .unwrap();
+        }
+        connection
+    }
+
+    #[test]
+    fn migrate_version_1() {
+        let mut connection = version_1(&[
+            ("bg7ijk", 1, "438.500/-7.6/T88.5", None),
+            ("kh6/w1aw/p", 3, "junk", Some("Night")),
+            ("bd4aa", 0, "", None),
+        ]);
+        migrate(&mut connection).unwrap();
+        assert_eq!(stored_version(&connection), Ok(SCHEMA_VERSION));
+
+        let count = |query: &str| {
+            connection
+                .query_row(query, [], |row| row.get::<_, i64>(0))
+                .unwrap()
+        };
+        assert_eq!(count("SELECT COUNT(*) FROM qsl"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM live_qsl"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM eyeball_qsl"), 1);
+
+        let text = |query: &str| {
+            connection
+                .query_row(query, [], |row| row.get::<_, Option<String>>(0))
+                .unwrap()
+        };
+        assert_eq!(
+            text("SELECT mode FROM qsl WHERE id = 1").as_deref(),
+            Some("FM")
+        );
+        assert_eq!(
+            text("SELECT mode FROM qsl WHERE id = 2").as_deref(),
+            Some("CW")
+        );
+        assert_eq!(
+            text("SELECT mode FROM qsl WHERE id = 3").as_deref(),
+            Some("EYEBALL")
+        );
+        // Callsigns are upper case with the base call beside them.
+        assert_eq!(
+            text("SELECT call_number FROM qsl WHERE id = 1").as_deref(),
+            Some("BG7IJK")
+        );
+        assert_eq!(
+            text("SELECT base_call FROM qsl WHERE id = 1").as_deref(),
+            Some("BG7IJK")
+        );
+        assert_eq!(
+            text("SELECT call_number FROM qsl WHERE id = 2").as_deref(),
+            Some("KH6/W1AW/P")
+        );
+        assert_eq!(
+            text("SELECT base_call FROM qsl WHERE id = 2").as_deref(),
+            Some("W1AW")
+        );
+        assert_eq!(
+            text("SELECT state FROM qsl WHERE id = 1").as_deref(),
+            Some("GD")
+        );
+        assert_eq!(text("SELECT state FROM qsl WHERE id = 2"), None);
+
+        let real = |query: &str| {
+            connection
+                .query_row(query, [], |row| row.get::<_, Option<f64>>(0))
+                .unwrap()
+        };
+        let tx = real("SELECT freq_tx FROM qsl WHERE id = 1").unwrap();
+        assert!((tx - 430.9).abs() < 1e-9, "{tx}");
+        assert_eq!(real("SELECT freq_rx FROM qsl WHERE id = 1"), Some(438.5));
+        assert_eq!(
+            text("SELECT tone FROM qsl WHERE id = 1").as_deref(),
+            Some("T88.5")
+        );
+        assert_eq!(text("SELECT note FROM qsl WHERE id = 1"), None);
+        // Text that is no frequency is kept in the note.
+        assert_eq!(real("SELECT freq_tx FROM qsl WHERE id = 2"), None);
+        assert_eq!(
+            text("SELECT note FROM qsl WHERE id = 2").as_deref(),
+            Some("Night Frequency: junk")
+        );
+
+        // Datetimes stay as logged until the owner converts them once.
+        assert_eq!(
+            text("SELECT datetime FROM qsl WHERE id = 1").as_deref(),
+            Some("2024-05-01 12:00:00")
+        );
+        assert_eq!(
+            text("SELECT time_zone FROM setting").as_deref(),
+            Some("+00:00")
+        );
+        assert_eq!(count("SELECT datetimes_converted FROM setting"), 0);
+    }
+
+    #[test]
+    fn migrate_twice() {
+        let mut connection = version_1(&[("bg7ijk", 1, "438.500", None)]);
+        migrate(&mut connection).unwrap();
+        connection
+            .execute("UPDATE qsl SET note = 'kept'", [])
+            .unwrap();
+        migrate(&mut connection).unwrap();
+        let note = connection
+            .query_row("SELECT note FROM qsl", [], |row| row.get::<_, String>(0))
+            .unwrap();
+        assert_eq!(note, "kept");
+    }
+
+    #[test]
+    fn migrate_empty_logbook() {
+        let mut connection = version_1(&[]);
+        migrate(&mut connection).unwrap();
+        let converted = connection
+            .query_row("SELECT datetimes_converted FROM setting", [], |row| {
+                row.get::<_, bool>(0)
+            })
+            .unwrap();
+        assert!(converted);
+    }
+
+    #[test]
+    fn refuse_newer_schema() {
+        let mut connection = version_1(&[]);
+        connection
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        let error = migrate(&mut connection).unwrap_err();
+        assert!(error.contains("newer than supported"));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::qsl_callsign::Callsign;
+use std::fmt::Display;
+
+/// Province level division of China, the ADIF STATE of DXCC entity 318.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Province {
+    /// ADIF STATE, e.g. GD
+    pub(crate) code: &'static str,
+    pub(crate) name: &'static str,
+    pub(crate) name_zh: &'static str,
+}
+
+/// DXCC entity code of China.
+pub const CHINA_DXCC: u32 = 318;
+
+/// Every province, in the order of their call areas.
+pub const PROVINCES: [Province; 31] = [
+    province("BJ", "Beijing", "北京"),
+    province("HL", "Heilongjiang", "黑龙江"),
+    province("JL", "Jilin", "吉林"),
+    province("LN", "Liaoning", "辽宁"),
+    province("TJ", "Tianjin", "天津"),
+    province("NM", "Nei Mongol", "内蒙古"),
+    province("HE", "Hebei", "河北"),
+    province("SX", "Shanxi", "山西"),
+    province("SH", "Shanghai", "上海"),
+    province("SD", "Shandong", "山东"),
+    province("JS", "Jiangsu", "江苏"),
+    province("ZJ", "Zhejiang", "浙江"),
+    province("JX", "Jiangxi", "江西"),
+    province("FJ", "Fujian", "福建"),
+    province("AH", "Anhui", "安徽"),
+    province("HA", "Henan", "河南"),
+    province("HB", "Hubei", "湖北"),
+    province("HN", "Hunan", "湖南"),
+    province("GD", "Guangdong", "广东"),
+    province("GX", "Guangxi", "广西"),
+    province("HI", "Hainan", "海南"),
+    province("SC", "Sichuan", "四川"),
+    province("CQ", "Chongqing", "重庆"),
+    province("GZ", "Guizhou", "贵州"),
+    province("YN", "Yunnan", "云南"),
+    province("SN", "Shaanxi", "陕西"),
+    province("GS", "Gansu", "甘肃"),
+    province("NX", "Ningxia", "宁夏"),
+    province("QH", "Qinghai", "青海"),
+    province("XJ", "Xinjiang", "新疆"),
+    province("XZ", "Xizang", "西藏"),
+];
+
+/// Call area digit and the range of the first suffix letter of each province.
+const DISTRICTS: [(char, char, char, &str); 31] = [
+    ('1', 'A', 'Z', "BJ"),
+    ('2', 'A', 'H', "HL"),
+    ('2', 'I', 'P', "JL"),
+    ('2', 'Q', 'X', "LN"),
+    ('3', 'A', 'F', "TJ"),
+    ('3', 'G', 'L', "NM"),
+    ('3', 'M', 'R', "HE"),
+    ('3', 'S', 'X', "SX"),
+    ('4', 'A', 'H', "SH"),
+    ('4', 'I', 'P', "SD"),
+    ('4', 'Q', 'X', "JS"),
+    ('5', 'A', 'H', "ZJ"),
+    ('5', 'I', 'P', "JX"),
+    ('5', 'Q', 'X', "FJ"),
+    ('6', 'A', 'H', "AH"),
+    ('6', 'I', 'P', "HA"),
+    ('6', 'Q', 'X', "HB"),
+    ('7', 'A', 'H', "HN"),
+    ('7', 'I', 'P', "GD"),
+    ('7', 'Q', 'X', "GX"),
+    ('7', 'Y', 'Z', "HI"),
+    ('8', 'A', 'F', "SC"),
+    ('8', 'G', 'L', "CQ"),
+    ('8', 'M', 'R', "GZ"),
+    ('8', 'S', 'X', "YN"),
+    ('9', 'A', 'F', "SN"),
+    ('9', 'G', 'L', "GS"),
+    ('9', 'M', 'R', "NX"),
+    ('9', 'S', 'X', "QH"),
+    ('0', 'A', 'F', "XJ"),
+    ('0', 'G', 'L', "XZ"),
+];
+
+/// Second letters of the B prefixes that belong to China. BM-BQ, BU-BX are
+/// Taiwan and Pratas, BS7 is Scarborough Reef.
+const CHINA_PREFIX_LETTERS: &str = "ABCDEFGHIJKLRTYZ";
+
+const fn province(code: &'static str, name: &'static str, name_zh: &'static str) -> Province {
+    Province {
+        code,
+        name,
+        name_zh,
+    }
+}
+
+impl Province {
+    pub fn from_code(code: &str) -> Option<&'static Province> {
+        PROVINCES.iter().find(|p| p.code.eq_ignore_ascii_case(code))
+    }
+
+    /// Province of a Chinese callsign from its call area and the first letter
+    /// of its suffix, e.g. BG7IJK is Guangdong. Callsigns operated away from
+    /// home, like BY1AA/9 or BY9/BG7AA, give none as the area no longer tells,
+    /// and so do maritime and aeronautical mobile.
+    pub fn from_callsign(text: &str) -> Option<&'static Province> {
+        let callsign = Callsign::parse(text).ok()?;
+        if callsign.prefix.is_some()
+            || callsign.area.is_some()
+            || callsign.dxcc_prefix().is_none()
+            || !is_china(&callsign.base)
+        {
+            return None;
+        }
+        let base = callsign.base.as_bytes();
+        let digit = base.iter().position(u8::is_ascii_digit)?;
+        let area = base[digit] as char;
+        let letter = *base.get(digit + 1)? as char;
+        let (_, _, _, code) = DISTRICTS
+            .iter()
+            .find(|(a, first, last, _)| *a == area && (*first..=*last).contains(&letter))?;
+        Province::from_code(code)
+    }
+}
+
+/// Province of a record's STATE when the contact is in China. STATE codes of
+/// other entities, like NM of New Mexico, are not provinces.
+pub fn province_of(
+    call_number: &str,
+    state: Option<&str>,
+    dxcc: Option<u32>,
+) -> Option<&'static Province> {
+    let province = Province::from_code(state?)?;
+    let in_china = match dxcc {
+        Some(dxcc) => dxcc == CHINA_DXCC,
+        None => match Callsign::parse(call_number) {
+            Ok(callsign) => is_china(callsign.prefix.as_ref().unwrap_or(&callsign.base)),
+            Err(_) => false,
+        },
+    };
+    in_china.then_some(province)
+}
+
+/// STATE the edit dialog saves. Left empty, or left at the province the old
+/// callsign gave, it is derived from the new callsign, so it follows a changed
+/// callsign. Anything else typed in is kept.
+pub fn edited_state(entered: &str, derived: Option<&str>, call_number: &str) -> Option<String> {
+    let entered = entered.trim().to_uppercase();
+    match entered.as_str() {
+        "" => None,
+        state if Some(state) == derived => None,
+        _ => Some(entered),
+    }
+    .or_else(|| Province::from_callsign(call_number).map(|p| p.code.to_string()))
+}
+
+/// Whether a prefix or call is issued by China, e.g. BG7 or BY1AA.
+fn is_china(call: &str) -> bool {
+    let bytes = call.as_bytes();
+    let letters = bytes.iter().take_while(|b| b.is_ascii_uppercase()).count();
+    match &bytes[..letters] {
+        [b'B'] => true,
+        [b'B', letter] => CHINA_PREFIX_LETTERS.as_bytes().contains(letter),
+        _ => false,
+    }
+}
+
+impl Display for Province {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name_zh, self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(callsign: &str) -> Option<&'static str> {
+        Province::from_callsign(callsign).map(|p| p.code)
+    }
+
+    #[test]
+    fn province_from_callsign() {
+        assert_eq!(code("BY1PK"), Some("BJ"));
+        assert_eq!(code("bg7ijk"), Some("GD"));
+        assert_eq!(code("BD7YA"), Some("HI"));
+        assert_eq!(code("BA4AA"), Some("SH"));
+        assert_eq!(code("BH8GA"), Some("CQ"));
+        assert_eq!(code("BI0GA"), Some("XZ"));
+        assert_eq!(code("BG7IJK/P"), Some("GD"));
+        // No province for a suffix letter outside the ranges.
+        assert_eq!(code("BG0ZZ"), None);
+    }
+
+    #[test]
+    fn callsign_away_from_home() {
+        assert_eq!(code("BY1AA/9"), None);
+        assert_eq!(code("BY9/BG7AA"), None);
+        assert_eq!(code("BG7IJK/MM"), None);
+    }
+
+    #[test]
+    fn callsign_outside_china() {
+        assert_eq!(code("W1AW"), None);
+        assert_eq!(code("BV2AA"), None);
+        assert_eq!(code("JA1AA"), None);
+        assert_eq!(code("NOCALL"), None);
+    }
+
+    #[test]
+    fn province_of_state() {
+        let code = |p: Option<&'static Province>| p.map(|p| p.code);
+        assert_eq!(code(province_of("BG7IJK", Some("gd"), None)), Some("GD"));
+        assert_eq!(code(province_of("KH6/W1AW", Some("HI"), None)), None);
+        assert_eq!(code(province_of("W1AW", Some("NM"), Some(291))), None);
+        assert_eq!(
+            code(province_of("W1AW", Some("NM"), Some(CHINA_DXCC))),
+            Some("NM")
+        );
+        assert_eq!(code(province_of("BG7IJK", Some("XX"), None)), None);
+        assert_eq!(code(province_of("BG7IJK", None, None)), None);
+    }
+
+    #[test]
+    fn state_follows_changed_callsign() {
+        // Derived from the old callsign, so it follows the new one.
+        assert_eq!(
+            edited_state("GD", Some("GD"), "BA4AA").as_deref(),
+            Some("SH")
+        );
+        assert_eq!(
+            edited_state(" ", Some("GD"), "BA4AA").as_deref(),
+            Some("SH")
+        );
+        assert_eq!(edited_state("", None, "W1AW"), None);
+        // Typed in by hand, so it is kept.
+        assert_eq!(
+            edited_state("hn", Some("GD"), "BA4AA").as_deref(),
+            Some("HN")
+        );
+        assert_eq!(edited_state("CA", None, "W1AW").as_deref(), Some("CA"));
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt::Display;
+
+/// A callsign operated from this logbook, e.g. the home call, a club call or
+/// a portable suffix, with the station defaults of new records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StationProfile {
+    pub(crate) id: i32,
+    /// ADIF STATION_CALLSIGN
+    pub(crate) callsign: String,
+    /// ADIF OPERATOR, who was at the radio when it differs from the callsign
+    pub(crate) operator: Option<String>,
+    pub(crate) qth: Option<String>,
+    pub(crate) grid: Option<String>,
+    pub(crate) rig: Option<String>,
+    pub(crate) ant: Option<String>,
+    pub(crate) watt: Option<f32>,
+}
+
+impl Display for StationProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.operator {
+            Some(operator) if *operator != self.callsign => {
+                write!(f, "{} (op. {})", self.callsign, operator)
+            }
+            _ => write!(f, "{}", self.callsign),
+        }
+    }
+}
+
+/// Named set of equipment that fills my side of a new record, e.g. "home HF".
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquipmentPreset {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) qth: Option<String>,
+    pub(crate) rig: Option<String>,
+    pub(crate) ant: Option<String>,
+    pub(crate) watt: Option<f32>,
+}
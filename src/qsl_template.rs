@@ -1,15 +1,31 @@
-use crate::qsl_type::QSL;
+use crate::qsl_province::Province;
+use crate::qsl_type::{OnAirStatistics, QSL};
 use askama::Template;
+use chrono::FixedOffset;
+use std::collections::HashMap;
 
 #[derive(Template)]
 #[template(path = "template.html")]
 pub struct RecordTemplate<'a> {
     pub callsign: &'a str,
     pub datetime: &'a str,
+    /// Zone the record times are shown in
+    pub time_zone: &'a FixedOffset,
+    pub on_air: &'a OnAirStatistics,
+    /// Every province with its number of contacts
+    pub provinces: &'a Vec<(&'static Province, usize)>,
+    /// Images copied next to the page, by record id
+    pub images: &'a HashMap<i32, Vec<String>>,
     pub records_formal: &'a Vec<QSL>,
     pub records_eyeball: &'a Vec<QSL>,
 }
 
+impl RecordTemplate<'_> {
+    fn images_of(&self, id: &i32) -> &[String] {
+        self.images.get(id).map_or(&[], |images| images.as_slice())
+    }
+}
+
 mod filters {
     pub fn display_some<T>(value: &Option<T>, _: &dyn askama::Values) -> askama::Result<String>
     where
@@ -1,46 +1,389 @@
-use crate::qsl_adif_helper::adif_generate_line;
-use chrono::NaiveDateTime;
+use crate::qsl_adif_helper::{ADIF_MODES, adif_generate_line};
+use crate::qsl_callsign::Callsign;
+use crate::qsl_card::{QSLCard, QSLRoute, QSLService};
+use crate::qsl_country::Country;
+use crate::qsl_frequency::{Frequency, fmt_mhz};
+use crate::qsl_grid::{
+    distance_and_bearing, format_adif_location, grid_to_lat_lon, lat_lon_to_grid, normalize_grid,
+    parse_adif_location,
+};
+use crate::qsl_province::{Province, province_of};
+use crate::qsl_station::StationProfile;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeDelta, Utc};
+use std::collections::HashMap;
 use std::fmt::Display;
 
 #[derive(PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Usage {
     HTML,
     TYPST,
     ADIF,
+    CABRILLO,
+    IMPORT,
     UI,
 }
 
+/// Records shown in the table.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Mode {
-    EYEBALL,
-    FM,
-    SSB,
-    CW,
-    FTB,
-    OTHER,
+pub enum QSLFilter {
+    All,
+    /// The counterpart is waiting for my card, see `QSLCard::is_owed`
+    CardsOwed,
+    /// I am waiting for the counterpart's card, see `QSLCard::is_awaited`
+    CardsAwaited,
+    /// Contacts with one station under any prefix or suffix, by base call
+    Callsign(String),
+    /// Records with a tag, e.g. one event
+    Tag(String),
+    /// Records matching the criteria of the search view
+    Search(QSLSearch),
 }
 
-impl Display for Mode {
+/// Eyeball meetings, on-air contacts or both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RecordKind {
+    #[default]
+    All,
+    Eyeball,
+    OnAir,
+}
+
+impl RecordKind {
+    pub const ALL: [RecordKind; 3] = [RecordKind::All, RecordKind::Eyeball, RecordKind::OnAir];
+
+    /// The kind after this one, back to the first after the last.
+    pub fn next(&self) -> RecordKind {
+        match self {
+            RecordKind::All => RecordKind::Eyeball,
+            RecordKind::Eyeball => RecordKind::OnAir,
+            RecordKind::OnAir => RecordKind::All,
+        }
+    }
+}
+
+impl Display for RecordKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let str = match self {
-            Mode::EYEBALL => "EYEBALL".to_string(),
-            Mode::FM => "FM".to_string(),
-            Mode::SSB => "SSB".to_string(),
-            Mode::CW => "CW".to_string(),
-            Mode::FTB => "FTB".to_string(),
-            Mode::OTHER => "OTHER".to_string(),
+        match self {
+            RecordKind::All => write!(f, "All"),
+            RecordKind::Eyeball => write!(f, "Eyeball"),
+            RecordKind::OnAir => write!(f, "On-air"),
+        }
+    }
+}
+
+/// Criteria of the search view, every one that is set has to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QSLSearch {
+    /// Part of the callsign, or its start when `callsign_prefix` is set
+    pub(crate) callsign: Option<String>,
+    pub(crate) callsign_prefix: bool,
+    /// ADIF MODE or EYEBALL
+    pub(crate) mode: Option<String>,
+    /// ADIF band, e.g. 2m
+    pub(crate) band: Option<String>,
+    /// Start of the first day included, in the time zone it was entered in
+    pub(crate) from: Option<DateTime<FixedOffset>>,
+    /// Start of the last day included
+    pub(crate) to: Option<DateTime<FixedOffset>>,
+    pub(crate) kind: RecordKind,
+    /// Part of the note
+    pub(crate) note: Option<String>,
+}
+
+impl Display for QSLSearch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut criteria = Vec::new();
+        if let Some(callsign) = &self.callsign {
+            match self.callsign_prefix {
+                true => criteria.push(format!("{callsign}*")),
+                false => criteria.push(format!("*{callsign}*")),
+            }
+        }
+        if let Some(mode) = &self.mode {
+            criteria.push(mode.clone());
+        }
+        if let Some(band) = &self.band {
+            criteria.push(band.clone());
+        }
+        if self.from.is_some() || self.to.is_some() {
+            let date = |d: &Option<DateTime<FixedOffset>>| {
+                d.map(|d| d.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default()
+            };
+            criteria.push(format!("{}..{}", date(&self.from), date(&self.to)));
+        }
+        if self.kind != RecordKind::All {
+            criteria.push(self.kind.to_string());
+        }
+        if let Some(note) = &self.note {
+            criteria.push(format!("note \"{note}\""));
+        }
+        match criteria.is_empty() {
+            true => write!(f, "everything"),
+            false => write!(f, "{}", criteria.join(", ")),
+        }
+    }
+}
+
+impl Display for QSLFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QSLFilter::All => write!(f, "All records"),
+            QSLFilter::CardsOwed => write!(f, "Cards owed"),
+            QSLFilter::CardsAwaited => write!(f, "Cards awaited"),
+            QSLFilter::Callsign(base_call) => write!(f, "Callsign {base_call}"),
+            QSLFilter::Tag(tag) => write!(f, "Tag {tag}"),
+            QSLFilter::Search(search) => write!(f, "Search {search}"),
+        }
+    }
+}
+
+/// Read a UTC offset such as "+08:00", "+8", "-0530", "UTC+8" or "UTC".
+pub fn parse_time_zone(text: &str) -> Result<FixedOffset, String> {
+    let upper = text.trim().to_uppercase();
+    let offset = upper
+        .strip_prefix("UTC")
+        .or(upper.strip_prefix("GMT"))
+        .unwrap_or(&upper)
+        .trim();
+    if offset.is_empty() || offset == "Z" {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+
+    let (sign, rest) = if let Some(rest) = offset.strip_prefix('+') {
+        (1, rest)
+    } else if let Some(rest) = offset.strip_prefix('-') {
+        (-1, rest)
+    } else {
+        return Err(format!(
+            "Time zone {text} should start with + or -, e.g. +08:00."
+        ));
+    };
+    if rest.is_empty() || !rest.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return Err(format!("Time zone {text} should be written like +08:00."));
+    }
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() > 2 => rest.split_at(rest.len() - 2),
+        None => (rest, "0"),
+    };
+    let hours = hours
+        .parse::<i32>()
+        .map_err(|e| format!("Cannot read hours of time zone {text}: {e}"))?;
+    let minutes = minutes
+        .parse::<i32>()
+        .map_err(|e| format!("Cannot read minutes of time zone {text}: {e}"))?;
+    if !(0..60).contains(&minutes) {
+        return Err(format!(
+            "Minutes of time zone {text} should be less than 60."
+        ));
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+        .ok_or(format!("Time zone {text} is out of range."))
+}
+
+/// Total time on air as "3 h 25 min", or "12 min" under an hour.
+pub fn fmt_duration(duration: &TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    match minutes / 60 {
+        0 => format!("{} min", minutes),
+        hours => format!("{} h {} min", hours, minutes % 60),
+    }
+}
+
+/// Start and end of one contact in UTC.
+pub type TimeSpan = (DateTime<Utc>, DateTime<Utc>);
+
+/// On-air time of the logbook, counted from contacts with an end time.
+/// Overlapping contacts, e.g. several stations in one net session, are counted once.
+pub struct OnAirStatistics {
+    /// Contacts with an end time
+    pub(crate) timed_contacts: usize,
+    pub(crate) total: TimeDelta,
+    pub(crate) longest: TimeDelta,
+}
+
+impl OnAirStatistics {
+    /// Build from (start, end) pairs ordered by start.
+    pub fn from_spans(spans: &[TimeSpan]) -> OnAirStatistics {
+        let mut total = TimeDelta::zero();
+        let mut longest = TimeDelta::zero();
+        let mut current: Option<TimeSpan> = None;
+        for (start, end) in spans {
+            longest = longest.max(*end - *start);
+            current = match current {
+                Some((current_start, current_end)) if *start <= current_end => {
+                    Some((current_start, current_end.max(*end)))
+                }
+                Some((current_start, current_end)) => {
+                    total += current_end - current_start;
+                    Some((*start, *end))
+                }
+                None => Some((*start, *end)),
+            };
+        }
+        if let Some((current_start, current_end)) = current {
+            total += current_end - current_start;
+        }
+
+        OnAirStatistics {
+            timed_contacts: spans.len(),
+            total,
+            longest,
+        }
+    }
+}
+
+impl Display for OnAirStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} on air in {} timed contact(s), longest {}",
+            fmt_duration(&self.total),
+            self.timed_contacts,
+            fmt_duration(&self.longest)
+        )
+    }
+}
+
+/// ADIF MODE with an optional SUBMODE, both taken from `ADIF_MODES`.
+/// EYEBALL and OTHER are our own values and never written to ADIF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mode {
+    mode: &'static str,
+    submode: Option<&'static str>,
+}
+
+impl Mode {
+    /// Met in person, not a radio contact.
+    pub const EYEBALL: Mode = Mode {
+        mode: "EYEBALL",
+        submode: None,
+    };
+    /// Records from before the ADIF catalogue whose mode was not one of FM/SSB/CW/FT8.
+    pub const OTHER: Mode = Mode {
+        mode: "OTHER",
+        submode: None,
+    };
+
+    /// Look up a mode and submode in the catalogue, case insensitive.
+    pub fn new(mode: &str, submode: Option<&str>) -> Result<Mode, String> {
+        let mode = mode.trim().to_uppercase();
+        for special in [Mode::EYEBALL, Mode::OTHER] {
+            if mode == special.mode {
+                return match submode {
+                    None => Ok(special),
+                    Some(submode) => Err(format!("{mode} cannot have submode {submode}.")),
+                };
+            }
+        }
+
+        let (mode, submodes) = ADIF_MODES
+            .iter()
+            .find(|(m, _)| *m == mode)
+            .ok_or(format!("{mode} is not an ADIF mode."))?;
+        let submode = match submode {
+            None => None,
+            Some(submode) => {
+                let submode = submode.trim().to_uppercase();
+                Some(
+                    *submodes
+                        .iter()
+                        .find(|s| **s == submode)
+                        .ok_or(format!("{submode} is not a submode of {mode}."))?,
+                )
+            }
         };
-        write!(f, "{}", str)
+        Ok(Mode { mode, submode })
+    }
+
+    /// Map ADIF MODE and SUBMODE back to `Mode`. MODE may be left out when the
+    /// SUBMODE alone identifies it, e.g. SUBMODE FT4 is MODE MFSK.
+    pub fn from_adif(mode: Option<&str>, submode: Option<&str>) -> Result<Mode, String> {
+        match (mode, submode) {
+            // Our exports before the ADIF catalogue wrote FT8 as FTB.
+            (Some(mode), None) if mode.trim().eq_ignore_ascii_case("FTB") => Mode::new("FT8", None),
+            (Some(mode), submode) => Mode::new(mode, submode),
+            (None, Some(submode)) => {
+                let upper = submode.trim().to_uppercase();
+                match ADIF_MODES.iter().find(|(_, s)| s.contains(&upper.as_str())) {
+                    Some((mode, _)) => Mode::new(mode, Some(&upper)),
+                    None => Err(format!("{submode} is not an ADIF submode.")),
+                }
+            }
+            (None, None) => Err("MODE is missing".to_string()),
+        }
+    }
+
+    pub fn mode(&self) -> &'static str {
+        self.mode
+    }
+
+    pub fn submode(&self) -> Option<&'static str> {
+        self.submode
+    }
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.submode.unwrap_or(self.mode))
     }
 }
 
+/// What is known about a counterpart's operator, remembered by base call
+/// from the latest record that had it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contact {
+    pub(crate) name: Option<String>,
+    pub(crate) email: Option<String>,
+    pub(crate) address: Option<String>,
+    pub(crate) qsl_via: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct QSL {
     pub(crate) id: i32,
     pub(crate) call_number: String,
     pub(crate) mode: Mode,
-    pub(crate) freq: Option<String>,
-    pub(crate) datetime: NaiveDateTime,
+    pub(crate) freq: Option<Frequency>,
+    /// Start of the contact in UTC, ADIF QSO_DATE / TIME_ON.
+    /// Entered and shown in the logbook's time zone.
+    pub(crate) datetime: DateTime<Utc>,
+    /// End of the contact in UTC, ADIF QSO_DATE_OFF / TIME_OFF
+    pub(crate) datetime_off: Option<DateTime<Utc>>,
+    /// Paper card, ADIF QSL_SENT / QSLSDATE / QSL_RCVD / QSLRDATE
+    pub(crate) card_paper: QSLCard,
+    /// ADIF EQSL_QSL_SENT / EQSL_QSLSDATE / EQSL_QSL_RCVD / EQSL_QSLRDATE
+    pub(crate) card_eqsl: QSLCard,
+    /// ADIF LOTW_QSL_SENT / LOTW_QSLSDATE / LOTW_QSL_RCVD / LOTW_QSLRDATE
+    pub(crate) card_lotw: QSLCard,
+    /// ADIF QSL_SENT_VIA / QSL_RCVD_VIA, route of the paper cards
+    pub(crate) qsl_sent_via: Option<QSLRoute>,
+    pub(crate) qsl_rcvd_via: Option<QSLRoute>,
+    /// ADIF QSL_VIA, the counterpart's manager or address for cards
+    pub(crate) qsl_via: Option<String>,
+    /// Id of the station profile this contact was made from
+    pub(crate) station_profile: Option<i32>,
+    /// ADIF CONTEST_ID, e.g. CQ-WW-SSB
+    pub(crate) contest_id: Option<String>,
+    /// ADIF STX / SRX, serial numbers sent and received
+    pub(crate) stx: Option<u32>,
+    pub(crate) srx: Option<u32>,
+    /// ADIF STX_STRING / SRX_STRING, the rest of the exchange, e.g. a province
+    pub(crate) stx_string: Option<String>,
+    pub(crate) srx_string: Option<String>,
+    /// ADIF DXCC, entity code of the counterpart
+    pub(crate) dxcc: Option<u32>,
+    /// ADIF COUNTRY, name of the counterpart's DXCC entity
+    pub(crate) country: Option<String>,
+    /// ADIF CONT, continent of the counterpart
+    pub(crate) cont: Option<String>,
+    /// ADIF CQZ / ITUZ, zones of the counterpart
+    pub(crate) cqz: Option<u32>,
+    pub(crate) ituz: Option<u32>,
+    /// ADIF STATE, the counterpart's province in China, e.g. GD
+    pub(crate) state: Option<String>,
     /// ADIF RST_SENT, signal report sent to the contacted station
     /// My signal
     pub(crate) rst_me: Option<String>,
@@ -55,13 +398,32 @@ pub struct QSL {
     pub(crate) rig_counterpart: Option<String>,
     pub(crate) watt_counterpart: Option<f32>,
     pub(crate) ant_counterpart: Option<String>,
+    /// ADIF NAME / EMAIL / ADDRESS of the counterpart's operator
+    pub(crate) name_counterpart: Option<String>,
+    pub(crate) email_counterpart: Option<String>,
+    pub(crate) address_counterpart: Option<String>,
     pub(crate) note: Option<String>,
+    /// ADIF SAT_NAME, e.g. SO-50, RS-44. A record with a satellite is PROP_MODE SAT
+    pub(crate) sat_name: Option<String>,
+    /// ADIF SAT_MODE, e.g. V/U
+    pub(crate) sat_mode: Option<String>,
+    /// ADIF MY_GRIDSQUARE, Maidenhead locator of my station
+    pub(crate) grid_me: Option<String>,
+    /// ADIF GRIDSQUARE, Maidenhead locator of the counterpart
+    pub(crate) grid_counterpart: Option<String>,
+    /// ADIF MY_LAT / MY_LON in decimal degrees, north and east are positive
+    pub(crate) lat_me: Option<f64>,
+    pub(crate) lon_me: Option<f64>,
+    /// ADIF LAT / LON in decimal degrees, north and east are positive
+    pub(crate) lat_counterpart: Option<f64>,
+    pub(crate) lon_counterpart: Option<f64>,
 }
 
 impl QSL {
-    pub fn fmt_typst(&self) -> String {
-        let date = self.datetime.date();
-        let time = self.datetime.time().format("%H:%M");
+    pub fn fmt_typst(&self, time_zone: &FixedOffset) -> String {
+        let datetime = self.local_datetime(time_zone);
+        let date = datetime.date_naive();
+        let time = datetime.time().format("%H:%M");
         format!(
             r#"(
   call_number: "{}",
@@ -69,6 +431,7 @@ impl QSL {
   freq: "{}",
   date: "{}",
   time: "{}",
+  time_off: "{}",
   rst_me: "{}",
   qth_me: "{}",
   rig_me: "{}",
@@ -80,12 +443,18 @@ impl QSL {
   watt_counterpart: "{}",
   ant_counterpart: "{}",
   note: "{}",
+  satellite: "{}",
+  grid_me: "{}",
+  grid_counterpart: "{}",
+  distance: "{}",
 ),"#,
             self.call_number,
             self.mode,
-            self.freq.as_ref().map_or("", |f| f),
+            self.freq.as_ref().map_or("".to_string(), |f| f.to_string()),
             date,
             time,
+            self.local_datetime_off(time_zone)
+                .map_or("".to_string(), |d| d.format("%H:%M").to_string()),
             self.rst_me.as_ref().map_or("", |r| r),
             self.qth_me.as_ref().map_or("", |q| q),
             self.rig_me.as_ref().map_or("", |r| r),
@@ -97,35 +466,239 @@ impl QSL {
             self.watt_counterpart
                 .map_or("".to_string(), |w| w.to_string()),
             self.ant_counterpart.as_ref().map_or("", |a| a),
-            self.note.as_ref().map_or("", |n| n)
+            self.note.as_ref().map_or("", |n| n),
+            self.satellite_info().unwrap_or_default(),
+            self.my_grid().unwrap_or_default(),
+            self.counterpart_grid().unwrap_or_default(),
+            self.distance_info().unwrap_or_default()
         )
     }
-    pub fn fmt_adif(&self) -> String {
-        if self.mode == Mode::EYEBALL || self.mode == Mode::OTHER {
+    /// Build a new record from the fields of one ADIF record, as read by `adif_parse`.
+    pub fn from_adif(fields: &HashMap<String, String>) -> Result<QSL, String> {
+        let get = |key: &str| {
+            fields
+                .get(key)
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+        let get_watt = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(w) => w
+                .parse::<f32>()
+                .map(Some)
+                .map_err(|e| format!("Parse {key} failed: {e}")),
+        };
+        let get_grid = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(g) => normalize_grid(&g)
+                .map(Some)
+                .map_err(|e| format!("Parse {key} failed: {e}")),
+        };
+        let get_location = |key: &str, is_lat: bool| match get(key) {
+            None => Ok(None),
+            Some(l) => parse_adif_location(&l, is_lat)
+                .map(Some)
+                .map_err(|e| format!("Parse {key} failed: {e}")),
+        };
+        let get_route = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(r) => QSLRoute::from_adif(&r)
+                .map(Some)
+                .map_err(|e| format!("Parse {key} failed: {e}")),
+        };
+        let get_number = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(n) => n
+                .parse::<u32>()
+                .map(Some)
+                .map_err(|e| format!("Parse {key} failed: {e}")),
+        };
+        let get_freq = |key: &str| match get(key) {
+            None => Ok(None),
+            Some(f) => f
+                .parse::<f64>()
+                .map(Some)
+                .map_err(|e| format!("Parse {key} failed: {e}")),
+        };
+
+        let call_number = get("CALL").ok_or("CALL is missing")?;
+        let call_number = match Callsign::parse(&call_number) {
+            Ok(callsign) => callsign.to_string(),
+            Err(_) => call_number.to_uppercase(),
+        };
+        let mode = Mode::from_adif(get("MODE").as_deref(), get("SUBMODE").as_deref())?;
+
+        let date = get("QSO_DATE").ok_or("QSO_DATE is missing")?;
+        let date = NaiveDate::parse_from_str(&date, "%Y%m%d")
+            .map_err(|e| format!("Parse QSO_DATE {date} failed: {e}"))?;
+        let time = get("TIME_ON").ok_or("TIME_ON is missing")?;
+        let time = match time.len() {
+            4 => NaiveTime::parse_from_str(&time, "%H%M"),
+            _ => NaiveTime::parse_from_str(&time, "%H%M%S"),
+        }
+        .map_err(|e| format!("Parse TIME_ON {time} failed: {e}"))?;
+        let datetime = date.and_time(time).and_utc();
+
+        let datetime_off = match get("TIME_OFF") {
+            None => None,
+            Some(time_off) => {
+                let time_off = match time_off.len() {
+                    4 => NaiveTime::parse_from_str(&time_off, "%H%M"),
+                    _ => NaiveTime::parse_from_str(&time_off, "%H%M%S"),
+                }
+                .map_err(|e| format!("Parse TIME_OFF {time_off} failed: {e}"))?;
+                let datetime_off = match get("QSO_DATE_OFF") {
+                    Some(date_off) => NaiveDate::parse_from_str(&date_off, "%Y%m%d")
+                        .map_err(|e| format!("Parse QSO_DATE_OFF {date_off} failed: {e}"))?
+                        .and_time(time_off)
+                        .and_utc(),
+                    // Without QSO_DATE_OFF, an end before the start is past midnight.
+                    None => {
+                        let datetime_off = date.and_time(time_off).and_utc();
+                        if datetime_off < datetime {
+                            datetime_off + TimeDelta::days(1)
+                        } else {
+                            datetime_off
+                        }
+                    }
+                };
+                if datetime_off < datetime {
+                    return Err(format!(
+                        "QSO_DATE_OFF / TIME_OFF {datetime_off} is before the start {datetime}"
+                    ));
+                }
+                Some(datetime_off)
+            }
+        };
+
+        Ok(QSL {
+            id: 0,
+            call_number,
+            mode,
+            freq: match get_freq("FREQ")? {
+                Some(tx) => Some(Frequency {
+                    tx,
+                    rx: get_freq("FREQ_RX")?.filter(|rx| (rx - tx).abs() > 1e-9),
+                    tone: None,
+                }),
+                None => None,
+            },
+            datetime,
+            datetime_off,
+            card_paper: QSLCard::from_adif(fields, QSLService::Paper)?,
+            card_eqsl: QSLCard::from_adif(fields, QSLService::Eqsl)?,
+            card_lotw: QSLCard::from_adif(fields, QSLService::LoTW)?,
+            qsl_sent_via: get_route("QSL_SENT_VIA")?,
+            qsl_rcvd_via: get_route("QSL_RCVD_VIA")?,
+            qsl_via: get("QSL_VIA"),
+            // STATION_CALLSIGN / OPERATOR are matched to a profile by the manager.
+            station_profile: None,
+            contest_id: get("CONTEST_ID"),
+            stx: get_number("STX")?,
+            srx: get_number("SRX")?,
+            stx_string: get("STX_STRING"),
+            srx_string: get("SRX_STRING"),
+            dxcc: get_number("DXCC")?,
+            country: get("COUNTRY"),
+            cont: get("CONT").map(|c| c.to_uppercase()),
+            cqz: get_number("CQZ")?,
+            ituz: get_number("ITUZ")?,
+            state: get("STATE").map(|s| s.to_uppercase()),
+            rst_me: get("RST_SENT"),
+            qth_me: get("MY_QTH").or(get("MY_CITY")),
+            rig_me: get("MY_RIG"),
+            watt_me: get_watt("TX_PWR")?,
+            ant_me: get("MY_ANT"),
+            rst_counterpart: get("RST_RCVD"),
+            qth_counterpart: get("QTH"),
+            rig_counterpart: get("RIG"),
+            watt_counterpart: get_watt("RX_PWR")?,
+            ant_counterpart: get("ANT"),
+            name_counterpart: get("NAME"),
+            email_counterpart: get("EMAIL"),
+            address_counterpart: get("ADDRESS"),
+            note: get("NOTES").or(get("COMMENT")),
+            sat_name: get("SAT_NAME"),
+            sat_mode: get("SAT_MODE"),
+            grid_me: get_grid("MY_GRIDSQUARE")?,
+            grid_counterpart: get_grid("GRIDSQUARE")?,
+            lat_me: get_location("MY_LAT", true)?,
+            lon_me: get_location("MY_LON", false)?,
+            lat_counterpart: get_location("LAT", true)?,
+            lon_counterpart: get_location("LON", false)?,
+        })
+    }
+
+    pub fn fmt_adif(&self, station: Option<&StationProfile>) -> String {
+        if self.mode == Mode::EYEBALL {
             eprintln!(
-                "Record {} is a EYEBALL / OTHER mode record, it will not output.",
-                self.mode
+                "Record {} is a EYEBALL record, it will not output.",
+                self.id
+            );
+            return "".to_string();
+        }
+        if self.mode == Mode::OTHER {
+            eprintln!(
+                "Record {} has no ADIF mode, choose one in the editor to output it.",
+                self.id
             );
             return "".to_string();
         }
         let mut str = adif_generate_line("CALL", &self.call_number).to_string();
-        str.push_str(&adif_generate_line("MODE", &self.mode.to_string()));
+        if let Some(station) = station {
+            str.push_str(&adif_generate_line("STATION_CALLSIGN", &station.callsign));
+            if let Some(operator) = &station.operator {
+                str.push_str(&adif_generate_line("OPERATOR", operator));
+            }
+        }
+        str.push_str(&adif_generate_line("MODE", self.mode.mode()));
+        if let Some(submode) = self.mode.submode() {
+            str.push_str(&adif_generate_line("SUBMODE", submode));
+        }
         str.push_str(&adif_generate_line(
             "QSO_DATE",
-            &self.datetime.date().format("%Y%m%d").to_string(),
+            &self.datetime.format("%Y%m%d").to_string(),
         ));
         str.push_str(&adif_generate_line(
             "TIME_ON",
-            &self.datetime.time().format("%H%M").to_string(),
+            &self.datetime.format("%H%M").to_string(),
         ));
-        match self.get_band() {
-            Ok(band) => str.push_str(&adif_generate_line("BAND", band.as_ref())),
-            Err(e) => {
-                eprintln!("Failed to parse band in record {}: {e}", self.id);
-            }
+        if let Some(datetime_off) = &self.datetime_off {
+            str.push_str(&adif_generate_line(
+                "QSO_DATE_OFF",
+                &datetime_off.format("%Y%m%d").to_string(),
+            ));
+            str.push_str(&adif_generate_line(
+                "TIME_OFF",
+                &datetime_off.format("%H%M").to_string(),
+            ));
         }
         if let Some(freq) = &self.freq {
-            str.push_str(&adif_generate_line("FREQ", freq.as_ref()))
+            match freq.band() {
+                Ok(band) => str.push_str(&adif_generate_line("BAND", band)),
+                Err(e) => {
+                    eprintln!("Failed to parse band in record {}: {e}", self.id);
+                }
+            }
+            str.push_str(&adif_generate_line("FREQ", &fmt_mhz(freq.tx)));
+            match freq.rx_band() {
+                Some(Ok(band)) => str.push_str(&adif_generate_line("BAND_RX", band)),
+                Some(Err(e)) => {
+                    eprintln!("Failed to parse rx band in record {}: {e}", self.id);
+                }
+                None => {}
+            }
+            if let Some(rx) = freq.rx {
+                str.push_str(&adif_generate_line("FREQ_RX", &fmt_mhz(rx)));
+            }
+        }
+        if let Some(sat_name) = &self.sat_name {
+            str.push_str(&adif_generate_line("PROP_MODE", "SAT"));
+            str.push_str(&adif_generate_line("SAT_NAME", sat_name));
+        }
+        if let Some(sat_mode) = &self.sat_mode {
+            str.push_str(&adif_generate_line("SAT_MODE", sat_mode));
         }
 
         if let Some(rst_me) = &self.rst_me {
@@ -134,6 +707,21 @@ impl QSL {
         if let Some(rst_counterpart) = &self.rst_counterpart {
             str.push_str(&adif_generate_line("RST_RCVD", rst_counterpart));
         }
+        if let Some(contest_id) = &self.contest_id {
+            str.push_str(&adif_generate_line("CONTEST_ID", contest_id));
+        }
+        if let Some(stx) = self.stx {
+            str.push_str(&adif_generate_line("STX", &stx.to_string()));
+        }
+        if let Some(srx) = self.srx {
+            str.push_str(&adif_generate_line("SRX", &srx.to_string()));
+        }
+        if let Some(stx_string) = &self.stx_string {
+            str.push_str(&adif_generate_line("STX_STRING", stx_string));
+        }
+        if let Some(srx_string) = &self.srx_string {
+            str.push_str(&adif_generate_line("SRX_STRING", srx_string));
+        }
 
         if let Some(qth_me) = &self.qth_me {
             str.push_str(&adif_generate_line("MY_QTH", qth_me));
@@ -159,6 +747,68 @@ impl QSL {
         if let Some(ant_counterpart) = &self.ant_counterpart {
             str.push_str(&adif_generate_line("ANT", ant_counterpart));
         }
+        if let Some(name_counterpart) = &self.name_counterpart {
+            str.push_str(&adif_generate_line("NAME", name_counterpart));
+        }
+        if let Some(email_counterpart) = &self.email_counterpart {
+            str.push_str(&adif_generate_line("EMAIL", email_counterpart));
+        }
+        if let Some(address_counterpart) = &self.address_counterpart {
+            str.push_str(&adif_generate_line("ADDRESS", address_counterpart));
+        }
+        if let Some(grid_me) = self.my_grid() {
+            str.push_str(&adif_generate_line("MY_GRIDSQUARE", &grid_me));
+        }
+        if let Some(grid_counterpart) = self.counterpart_grid() {
+            str.push_str(&adif_generate_line("GRIDSQUARE", &grid_counterpart));
+        }
+        if let Some(dxcc) = self.dxcc {
+            str.push_str(&adif_generate_line("DXCC", &dxcc.to_string()));
+        }
+        if let Some(country) = &self.country {
+            str.push_str(&adif_generate_line("COUNTRY", country));
+        }
+        if let Some(cont) = &self.cont {
+            str.push_str(&adif_generate_line("CONT", cont));
+        }
+        if let Some(cqz) = self.cqz {
+            str.push_str(&adif_generate_line("CQZ", &cqz.to_string()));
+        }
+        if let Some(ituz) = self.ituz {
+            str.push_str(&adif_generate_line("ITUZ", &ituz.to_string()));
+        }
+        if let Some(state) = &self.state {
+            str.push_str(&adif_generate_line("STATE", state));
+        }
+        if let Some((lat, lon)) = self.my_location() {
+            str.push_str(&adif_generate_line(
+                "MY_LAT",
+                &format_adif_location(lat, true),
+            ));
+            str.push_str(&adif_generate_line(
+                "MY_LON",
+                &format_adif_location(lon, false),
+            ));
+        }
+        if let Some((lat, lon)) = self.counterpart_location() {
+            str.push_str(&adif_generate_line("LAT", &format_adif_location(lat, true)));
+            str.push_str(&adif_generate_line(
+                "LON",
+                &format_adif_location(lon, false),
+            ));
+        }
+        for service in QSLService::ALL {
+            str.push_str(&self.card(service).fmt_adif(service));
+        }
+        if let Some(route) = self.qsl_sent_via {
+            str.push_str(&adif_generate_line("QSL_SENT_VIA", route.adif()));
+        }
+        if let Some(route) = self.qsl_rcvd_via {
+            str.push_str(&adif_generate_line("QSL_RCVD_VIA", route.adif()));
+        }
+        if let Some(qsl_via) = &self.qsl_via {
+            str.push_str(&adif_generate_line("QSL_VIA", qsl_via));
+        }
         if let Some(note) = &self.note {
             str.push_str(&adif_generate_line("NOTES", note));
         }
@@ -167,96 +817,226 @@ impl QSL {
         str
     }
 
-    fn get_band<'a>(&self) -> Result<impl AsRef<str>, String> {
-        let split = match self.freq.as_ref() {
-            None => {
-                return Err("Freq is none".to_string());
-            }
-            Some(str) => str.split("/").collect::<Vec<_>>(),
-        };
+    /// Replace the counterpart's entity and zones with those resolved from the callsign.
+    pub fn set_country(&mut self, country: Option<Country>) {
+        self.dxcc = country.as_ref().and_then(|c| c.dxcc);
+        self.country = country.as_ref().map(|c| c.name.clone());
+        self.cont = country.as_ref().map(|c| c.continent.clone());
+        self.cqz = country.as_ref().map(|c| c.cq_zone);
+        self.ituz = country.as_ref().map(|c| c.itu_zone);
+    }
 
-        if split.is_empty() {
-            return Err(
-                "Format not match, each parameter is divided with '/' with no spacebar surrounded."
-                    .to_string(),
-            );
+    /// Entity, continent and zones in one line, e.g. "China, AS, CQ 24, ITU 44".
+    pub fn country_info(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(country) = &self.country {
+            parts.push(country.clone());
+        }
+        if let Some(cont) = &self.cont {
+            parts.push(cont.clone());
         }
+        if let Some(cqz) = self.cqz {
+            parts.push(format!("CQ {cqz}"));
+        }
+        if let Some(ituz) = self.ituz {
+            parts.push(format!("ITU {ituz}"));
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
 
-        match split.first().unwrap().parse::<f32>() {
-            Ok(freq_mhz) => {
-                if (0.1357..=0.1378).contains(&freq_mhz) {
-                    Ok("2190m")
-                } else if (0.472..=0.479).contains(&freq_mhz) {
-                    Ok("630m")
-                } else if (0.501..=0.504).contains(&freq_mhz) {
-                    Ok("560m")
-                } else if (1.8..=2.0).contains(&freq_mhz) {
-                    Ok("160m")
-                } else if (3.5..=4.0).contains(&freq_mhz) {
-                    Ok("80m")
-                } else if (5.06..=5.45).contains(&freq_mhz) {
-                    Ok("60m")
-                } else if (7.0..=7.3).contains(&freq_mhz) {
-                    Ok("40m")
-                } else if (10.1..=10.15).contains(&freq_mhz) {
-                    Ok("30m")
-                } else if (14.0..=14.35).contains(&freq_mhz) {
-                    Ok("20m")
-                } else if (18.068..=18.168).contains(&freq_mhz) {
-                    Ok("17m")
-                } else if (21.0..=21.45).contains(&freq_mhz) {
-                    Ok("15m")
-                } else if (24.890..=24.99).contains(&freq_mhz) {
-                    Ok("12m")
-                } else if (28.0..=29.7).contains(&freq_mhz) {
-                    Ok("10m")
-                } else if (40.0..=45.0).contains(&freq_mhz) {
-                    Ok("8m")
-                } else if (50.0..=54.0).contains(&freq_mhz) {
-                    Ok("6m")
-                } else if (54.000001..=69.9).contains(&freq_mhz) {
-                    Ok("5m")
-                } else if (70.0..=71.0).contains(&freq_mhz) {
-                    Ok("4m")
-                } else if (144.0..=148.0).contains(&freq_mhz) {
-                    Ok("2m")
-                } else if (222.0..=225.0).contains(&freq_mhz) {
-                    Ok("1.25m")
-                } else if (420.0..=450.0).contains(&freq_mhz) {
-                    Ok("70cm")
-                } else if (902.0..=928.0).contains(&freq_mhz) {
-                    Ok("33cm")
-                } else if (1240.0..=1300.0).contains(&freq_mhz) {
-                    Ok("23cm")
-                } else if (2300.0..=2450.0).contains(&freq_mhz) {
-                    Ok("13cm")
-                } else if (3300.0..=3500.0).contains(&freq_mhz) {
-                    Ok("9cm")
-                } else if (5650.0..=5925.0).contains(&freq_mhz) {
-                    Ok("6cm")
-                } else if (10000.0..=10500.0).contains(&freq_mhz) {
-                    Ok("3cm")
-                } else if (24000.0..=24250.0).contains(&freq_mhz) {
-                    Ok("1.25cm")
-                } else if (47000.0..=47200.0).contains(&freq_mhz) {
-                    Ok("6mm")
-                } else if (75500.0..=81000.0).contains(&freq_mhz) {
-                    Ok("4mm")
-                } else if (119980.0..=123000.0).contains(&freq_mhz) {
-                    Ok("2.5mm")
-                } else if (134000.0..=149000.0).contains(&freq_mhz) {
-                    Ok("2mm")
-                } else if (241000.0..=250000.0).contains(&freq_mhz) {
-                    Ok("1mm")
-                } else if (300000.0..=7500000.0).contains(&freq_mhz) {
-                    Ok("submm")
-                } else {
-                    Err(format!(
-                        "Cannot parse {freq_mhz} because it is not in the standard's band range, as shown in III.B.4."
-                    ).to_string())
-                }
-            }
-            Err(e) => Err(e.to_string()),
+    /// Province of a contact in China, from its STATE.
+    pub fn province(&self) -> Option<&'static Province> {
+        province_of(&self.call_number, self.state.as_deref(), self.dxcc)
+    }
+
+    /// Province name in Chinese, or the STATE as it is for other entities.
+    pub fn state_info(&self) -> Option<String> {
+        match self.province() {
+            Some(province) => Some(province.name_zh.to_string()),
+            None => self.state.clone(),
+        }
+    }
+
+    /// Satellite name and mode in one line, for the HTML and Typst log.
+    pub fn satellite_info(&self) -> Option<String> {
+        let sat_name = self.sat_name.as_ref()?;
+        Some(match &self.sat_mode {
+            Some(sat_mode) => format!("{sat_name} ({sat_mode})"),
+            None => sat_name.clone(),
+        })
+    }
+
+    pub fn card(&self, service: QSLService) -> &QSLCard {
+        match service {
+            QSLService::Paper => &self.card_paper,
+            QSLService::Eqsl => &self.card_eqsl,
+            QSLService::LoTW => &self.card_lotw,
+        }
+    }
+
+    /// Card state of every service in use, e.g. "Paper: sent 2025-01-02; LoTW: received".
+    pub fn card_info(&self) -> Option<String> {
+        let info = QSLService::ALL
+            .iter()
+            .filter_map(|service| {
+                self.card(*service)
+                    .info()
+                    .map(|info| format!("{service}: {info}"))
+            })
+            .collect::<Vec<_>>();
+        if info.is_empty() {
+            None
+        } else {
+            Some(info.join("; "))
+        }
+    }
+
+    /// "owed" or "awaited" when a card of any service is, owed first.
+    pub fn card_state(&self) -> &'static str {
+        let cards = QSLService::ALL.map(|service| self.card(service));
+        if cards.iter().any(|card| card.is_owed()) {
+            "owed"
+        } else if cards.iter().any(|card| card.is_awaited()) {
+            "awaited"
+        } else {
+            ""
         }
     }
+
+    pub fn local_datetime(&self, time_zone: &FixedOffset) -> DateTime<FixedOffset> {
+        self.datetime.with_timezone(time_zone)
+    }
+
+    pub fn local_datetime_off(&self, time_zone: &FixedOffset) -> Option<DateTime<FixedOffset>> {
+        self.datetime_off.map(|d| d.with_timezone(time_zone))
+    }
+
+    pub fn my_location(&self) -> Option<(f64, f64)> {
+        Some((self.lat_me?, self.lon_me?))
+    }
+
+    pub fn counterpart_location(&self) -> Option<(f64, f64)> {
+        Some((self.lat_counterpart?, self.lon_counterpart?))
+    }
+
+    /// Locator of my station, the entered one or the one derived from my coordinates.
+    pub fn my_grid(&self) -> Option<String> {
+        self.grid_me.clone().or_else(|| {
+            self.my_location()
+                .map(|(lat, lon)| lat_lon_to_grid(lat, lon))
+        })
+    }
+
+    /// Locator of the counterpart, the entered one or the one derived from their coordinates.
+    pub fn counterpart_grid(&self) -> Option<String> {
+        self.grid_counterpart.clone().or_else(|| {
+            self.counterpart_location()
+                .map(|(lat, lon)| lat_lon_to_grid(lat, lon))
+        })
+    }
+
+    /// Distance and bearing between both stations, e.g. "1234 km 56°".
+    /// Coordinates are preferred over the grid centre when both are known.
+    pub fn distance_info(&self) -> Option<String> {
+        let from = match self.my_location() {
+            Some(location) => location,
+            None => grid_to_lat_lon(self.grid_me.as_ref()?).ok()?,
+        };
+        let to = match self.counterpart_location() {
+            Some(location) => location,
+            None => grid_to_lat_lon(self.grid_counterpart.as_ref()?).ok()?,
+        };
+        let (distance, bearing) = distance_and_bearing(from, to);
+        Some(format!("{distance:.0} km {bearing:.0}°"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qsl_adif_helper::adif_parse;
+
+    fn read_adif(content: &[u8]) -> QSL {
+        let records = adif_parse(content);
+        QSL::from_adif(records[0].as_ref().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn time_zone() {
+        let hours = |h: i32, m: i32| FixedOffset::east_opt(h * 3600 + m * 60).unwrap();
+        assert_eq!(parse_time_zone("+08:00"), Ok(hours(8, 0)));
+        assert_eq!(parse_time_zone("+8"), Ok(hours(8, 0)));
+        assert_eq!(parse_time_zone(" utc+8 "), Ok(hours(8, 0)));
+        assert_eq!(parse_time_zone("-0530"), Ok(hours(-5, -30)));
+        assert_eq!(parse_time_zone("GMT-3:30"), Ok(hours(-3, -30)));
+        assert_eq!(parse_time_zone("UTC"), Ok(hours(0, 0)));
+        assert_eq!(parse_time_zone("Z"), Ok(hours(0, 0)));
+        assert!(parse_time_zone("8").is_err());
+        assert!(parse_time_zone("+8h").is_err());
+        assert!(parse_time_zone("+08:60").is_err());
+        assert!(parse_time_zone("+25").is_err());
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(fmt_duration(&TimeDelta::minutes(12)), "12 min");
+        assert_eq!(fmt_duration(&TimeDelta::minutes(205)), "3 h 25 min");
+    }
+
+    #[test]
+    fn adif_round_trip() {
+        let qsl = read_adif(
+            b"<ADIF_VER:5>3.1.4 <EOH>\n\
+            <CALL:5>bg1aa <MODE:3>SSB <SUBMODE:3>USB <QSO_DATE:8>20250601 \
+            <TIME_ON:6>233000 <TIME_OFF:4>0015 <FREQ:6>14.250 <FREQ_RX:6>14.300 \
+            <RST_SENT:2>59 <RST_RCVD:2>57 <TX_PWR:2>50 <GRIDSQUARE:6>bl11BH <STATE:2>hi \
+            <QSL_RCVD:1>Y <QSLRDATE:8>20250701 <QSL_RCVD_VIA:1>B <NOTES:5>Hello <EOR>\n",
+        );
+        assert_eq!(qsl.call_number, "BG1AA");
+        assert_eq!(qsl.mode.to_string(), "USB");
+        assert_eq!(qsl.datetime.to_string(), "2025-06-01 23:30:00 UTC");
+        assert_eq!(
+            qsl.datetime_off.map(|d| d.to_string()),
+            Some("2025-06-02 00:15:00 UTC".to_string())
+        );
+        let freq = qsl.freq.clone().unwrap();
+        assert_eq!((freq.tx, freq.rx), (14.25, Some(14.3)));
+        assert_eq!(qsl.rst_counterpart.as_deref(), Some("57"));
+        assert_eq!(qsl.watt_me, Some(50.0));
+        assert_eq!(qsl.grid_counterpart.as_deref(), Some("BL11bh"));
+        assert_eq!(qsl.state.as_deref(), Some("HI"));
+        let received_date = NaiveDate::from_ymd_opt(2025, 7, 1);
+        assert_eq!(qsl.card_paper.received_date, received_date);
+
+        let output = qsl.fmt_adif(None);
+        assert!(
+            output.starts_with("<CALL:5>BG1AA <MODE:3>SSB <SUBMODE:3>USB <QSO_DATE:8>20250601 ")
+        );
+        assert!(output.contains("<TIME_ON:4>2330 <QSO_DATE_OFF:8>20250602 <TIME_OFF:4>0015 "));
+        assert!(output.contains("<BAND:3>20m <FREQ:5>14.25 <BAND_RX:3>20m <FREQ_RX:4>14.3 "));
+        assert!(output.contains("<GRIDSQUARE:6>BL11bh "));
+        assert!(output.contains("<QSL_RCVD:1>Y <QSLRDATE:8>20250701 <QSL_RCVD_VIA:1>B "));
+        assert!(output.ends_with("<NOTES:5>Hello <EOR>\n"));
+        assert_eq!(read_adif(output.as_bytes()).fmt_adif(None), output);
+    }
+
+    #[test]
+    fn adif_errors() {
+        let mut fields = HashMap::from([
+            ("CALL".to_string(), "BG1AA".to_string()),
+            ("QSO_DATE".to_string(), "20250601".to_string()),
+            ("TIME_ON".to_string(), "0100".to_string()),
+        ]);
+        assert_eq!(QSL::from_adif(&fields).unwrap_err(), "MODE is missing");
+        fields.insert("MODE".to_string(), "EYEBALL".to_string());
+        assert_eq!(QSL::from_adif(&fields).unwrap().fmt_adif(None), "");
+        fields.insert("TIME_ON".to_string(), "2500".to_string());
+        assert!(QSL::from_adif(&fields).is_err());
+        fields.remove("QSO_DATE");
+        assert_eq!(QSL::from_adif(&fields).unwrap_err(), "QSO_DATE is missing");
+    }
 }
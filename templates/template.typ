@@ -22,19 +22,23 @@
 ])
 
 #table(
-  columns: (auto, auto, 0.8fr, auto, 1.2fr, 1.2fr, 0.8fr, 0.7fr, 0.7fr, 2fr, 1.5fr, 1.5fr, 0.7fr, 0.7fr, 2.5fr),
+  columns: (auto, auto, 0.8fr, 0.8fr, auto, 1.2fr, 1.2fr, 0.8fr, 1.2fr, 0.7fr, 0.7fr, 2fr, 1fr, 1.2fr, 1.5fr, 1.5fr, 0.7fr, 0.7fr, 2.5fr),
   align: center + horizon,
   stroke: 0.5pt,
   table.header(
     table.cell(rowspan: 2, text(10pt)[序号]),
     table.cell(rowspan: 2, header-cell[日期][DATE]),
-    table.cell(rowspan: 2, header-cell[时间][TIME]),
+    table.cell(rowspan: 2, header-cell[时间][TIME UTC#time_zone]),
+    table.cell(rowspan: 2, header-cell[结束][TIME OFF]),
     table.cell(rowspan: 2, header-cell[天气][WX]),
     table.cell(rowspan: 2, header-cell[呼号][CALLSIGN]),
     table.cell(rowspan: 2, header-cell[频率][MHz]),
     table.cell(rowspan: 2, header-cell[模式][MODE]),
+    table.cell(rowspan: 2, header-cell[卫星][SAT]),
     table.cell(colspan: 2, header-cell[信号报告][RST]),
     table.cell(rowspan: 2, header-cell[电台位置][QTH]),
+    table.cell(rowspan: 2, header-cell[网格][GRID]),
+    table.cell(rowspan: 2, header-cell[距离方位][DIST / AZ]),
     table.cell(rowspan: 2, header-cell[设备][RIG]),
     table.cell(rowspan: 2, header-cell[天线][ANT]),
     table.cell(colspan: 2, header-cell[功率][PWR]),
@@ -50,13 +54,17 @@
       str(id),
       log.date,
       log.time,
+      get-optional(log, "time_off"),
       [],
       log.call_number,
       get-optional(log, "freq"),
       log.mode,
+      get-optional(log, "satellite"),
       get-optional(log, "rst_me"),
       get-optional(log, "rst_counterpart"),
       get-optional(log, "qth_counterpart"),
+      get-optional(log, "grid_counterpart"),
+      get-optional(log, "distance"),
       get-optional(log, "rig_me"),
       get-optional(log, "ant_me"),
       get-optional(log, "watt_me"),